@@ -0,0 +1,25 @@
+package serde_clj;
+
+/**
+ * An opaque reference to a Rust value (a {@code serde_clj::Handle}),
+ * which is dropped once this object has been garbage collected.
+ */
+public final class Handle {
+    private final long ptr;
+    private final String type;
+
+    private Handle(long ptr, String type) {
+        this.ptr = ptr;
+        this.type = type;
+    }
+
+    /** The Rust type name of the value. */
+    public String type() {
+        return type;
+    }
+
+    @Override
+    public String toString() {
+        return "#<Handle " + type + ">";
+    }
+}
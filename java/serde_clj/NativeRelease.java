@@ -0,0 +1,28 @@
+package serde_clj;
+
+import java.lang.ref.Cleaner;
+
+/**
+ * Releases Rust-owned memory once the Java object it backs has been
+ * garbage collected.
+ */
+public final class NativeRelease implements Runnable {
+    private static final Cleaner CLEANER = Cleaner.create();
+
+    private final long ptr;
+
+    private NativeRelease(long ptr) {
+        this.ptr = ptr;
+    }
+
+    public static void register(Object obj, long ptr) {
+        CLEANER.register(obj, new NativeRelease(ptr));
+    }
+
+    @Override
+    public void run() {
+        release(ptr);
+    }
+
+    private static native void release(long ptr);
+}
@@ -0,0 +1,32 @@
+package serde_clj;
+
+import clojure.lang.RestFn;
+
+/**
+ * A Clojure fn implemented by a Rust closure (a {@code serde_clj::RustFn}),
+ * which is dropped once this object has been garbage collected.
+ */
+public final class RustFn extends RestFn {
+    private final long ptr;
+
+    private RustFn(long ptr) {
+        this.ptr = ptr;
+    }
+
+    @Override
+    public int getRequiredArity() {
+        return 0;
+    }
+
+    @Override
+    protected Object doInvoke(Object args) {
+        return call(ptr, args);
+    }
+
+    @Override
+    public String toString() {
+        return "#<RustFn>";
+    }
+
+    private native Object call(long ptr, Object args);
+}
@@ -0,0 +1,44 @@
+package serde_clj;
+
+import clojure.lang.AFn;
+import clojure.lang.ArrayChunk;
+import clojure.lang.ChunkedCons;
+import clojure.lang.ISeq;
+import clojure.lang.LazySeq;
+
+/**
+ * A Rust iterator (from {@code serde_clj::lazy_seq}), realized as a chunked
+ * lazy seq. The iterator is dropped once this object has been garbage
+ * collected, i.e. once the seq has been fully realized or its unrealized
+ * rest is unreachable.
+ */
+public final class RustIter {
+    private final long ptr;
+    private final int chunkSize;
+
+    private RustIter(long ptr, int chunkSize) {
+        this.ptr = ptr;
+        this.chunkSize = chunkSize;
+    }
+
+    /** A lazy seq of the items not yet pulled; only call this once. */
+    public ISeq seq() {
+        return new LazySeq(new AFn() {
+            @Override
+            public Object invoke() {
+                Object[] items = next(ptr, chunkSize);
+                if (items.length == 0) {
+                    return null;
+                }
+                return new ChunkedCons(new ArrayChunk(items), seq());
+            }
+        });
+    }
+
+    @Override
+    public String toString() {
+        return "#<RustIter>";
+    }
+
+    private native Object[] next(long ptr, int n);
+}
@@ -0,0 +1,330 @@
+//! `ndarray` arrays as nested vectors or shaped primitive arrays
+//! (requires the `ndarray` feature).
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use ndarray::{Array, ArrayBase, ArrayViewD, Data, Dimension, IxDyn, RawData};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::buffer::{BufferElement, PrimitiveArray};
+use crate::marker;
+
+/// What our `Serializer` stashes to ask for `{:shape [..] :data ..}`
+/// (see `Encoder::shaped_arrays`).
+pub(crate) struct AsShaped;
+
+/// Serializes an array as nested sequences, or in the shaped form if
+/// our `Serializer` asks for it.
+struct Nd<'a, S: RawData, D>(&'a ArrayBase<S, D>);
+
+impl<'a, T, S, D> Serialize for Nd<'a, S, D>
+where
+    T: BufferElement + Serialize,
+    S: Data<Elem = T>,
+    D: Dimension,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        if marker::take::<AsShaped>().is_some() {
+            let mut st = serializer.serialize_struct("NdArray", 2)?;
+            st.serialize_field("shape", self.0.shape())?;
+            st.serialize_field("data", &PrimitiveArray(self.0.iter().copied().collect()))?;
+            st.end()
+        } else {
+            Nested(self.0.view().into_dyn()).serialize(serializer)
+        }
+    }
+}
+
+struct Nested<'a, T>(ArrayViewD<'a, T>);
+
+impl<'a, T: BufferElement + Serialize> Serialize for Nested<'a, T> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        if self.0.ndim() == 0 {
+            self.0.first().serialize(serializer)
+        } else {
+            serializer.collect_seq(self.0.outer_iter().map(Nested))
+        }
+    }
+}
+
+/// Collects the elements and shape of nested sequences.
+struct Builder<T> {
+    /// the length at each depth, once a sequence there has ended
+    shape: Vec<Option<usize>>,
+    /// the depth of the elements, once one has been seen
+    leaf: Option<usize>,
+    data: Vec<T>,
+    /// the result of the shaped form
+    shaped: Option<(Vec<usize>, Vec<T>)>,
+}
+
+/// Deserializes one level of nesting into the `Builder`.
+struct Level<'b, T> {
+    builder: &'b mut Builder<T>,
+    depth: usize,
+}
+
+impl<'b, T> Level<'b, T> {
+    fn element<'de, E, V>(self, value: V) -> Result<(), E>
+    where
+        T: BufferElement + de::Deserialize<'de>,
+        E: de::Error,
+        V: IntoDeserializer<'de, E>,
+    {
+        if *self.builder.leaf.get_or_insert(self.depth) != self.depth
+            || self.builder.shape.len() > self.depth
+        {
+            return Err(E::custom("ragged nested sequences"));
+        }
+        self.builder
+            .data
+            .push(T::deserialize(value.into_deserializer())?);
+        Ok(())
+    }
+}
+
+impl<'de, 'b, T: BufferElement + de::Deserialize<'de>> DeserializeSeed<'de> for Level<'b, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_newtype_struct(marker::NDARRAY, self)
+    }
+}
+
+impl<'de, 'b, T: BufferElement + de::Deserialize<'de>> Visitor<'de> for Level<'b, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("nested sequences of numbers or {:shape [..] :data [..]}")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+        self.element(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+        self.element(v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+        self.element(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let depth = self.depth;
+        if self.builder.leaf.is_some_and(|leaf| depth >= leaf) {
+            return Err(de::Error::custom("ragged nested sequences"));
+        }
+        if self.builder.shape.len() <= depth {
+            self.builder.shape.push(None);
+        }
+        let mut len = 0;
+        while seq
+            .next_element_seed(Level {
+                builder: &mut *self.builder,
+                depth: depth + 1,
+            })?
+            .is_some()
+        {
+            len += 1;
+        }
+        match self.builder.shape[depth] {
+            Some(expected) if expected != len => Err(de::Error::custom(format!(
+                "ragged nested sequences: {} elements where {} were expected",
+                len, expected
+            ))),
+            _ => {
+                self.builder.shape[depth] = Some(len);
+                Ok(())
+            }
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        if self.depth > 0 {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+        }
+        let mut shape: Option<Vec<usize>> = None;
+        let mut data: Option<PrimitiveArray<T>> = None;
+        while let Some(key) = map.next_key::<Field>()? {
+            match key {
+                Field::Shape => shape = Some(map.next_value()?),
+                Field::Data => data = Some(map.next_value()?),
+                Field::Other => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+            }
+        }
+        let shape = shape.ok_or_else(|| de::Error::missing_field("shape"))?;
+        let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
+        self.builder.shaped = Some((shape, data.0));
+        Ok(())
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // other deserializers, which don't know our marker
+        deserializer.deserialize_any(self)
+    }
+}
+
+/// The keys of the shaped form.
+enum Field {
+    Shape,
+    Data,
+    Other,
+}
+
+impl<'de> de::Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("`shape` or `data`")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+                Ok(match v {
+                    "shape" => Field::Shape,
+                    "data" => Field::Data,
+                    _ => Field::Other,
+                })
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+/// For use with `#[serde(with = "serde_clj::as_ndarray")]` on an
+/// `ndarray` array (`Array2<f64>`, `ArrayD<i32>`, etc.) of any
+/// [`BufferElement`](../trait.BufferElement.html) type, which serializes
+/// it as nested vectors (`[[1.0 2.0] [3.0 4.0]]`), or as
+/// `{:shape [2 2] :data <double[]>}` with
+/// [`Encoder::shaped_arrays`](../struct.Encoder.html#method.shaped_arrays).
+/// Deserialization accepts either form, checking that the nesting is
+/// rectangular and the number of dimensions and elements match. Other
+/// serializers see nested sequences.
+pub mod as_ndarray {
+    use std::marker::PhantomData;
+
+    use ndarray::{Array, ArrayBase, Data, Dimension};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{ArrayVisitor, Nd};
+    use crate::buffer::BufferElement;
+    use crate::marker;
+
+    pub fn serialize<T, S, D, Z>(value: &ArrayBase<S, D>, serializer: Z) -> Result<Z::Ok, Z::Error>
+    where
+        T: BufferElement + Serialize,
+        S: Data<Elem = T>,
+        D: Dimension,
+        Z: Serializer,
+    {
+        serializer.serialize_newtype_struct(marker::NDARRAY, &Nd(value))
+    }
+
+    pub fn deserialize<'de, T, D, Z>(deserializer: Z) -> Result<Array<T, D>, Z::Error>
+    where
+        T: BufferElement + Deserialize<'de>,
+        D: Dimension,
+        Z: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(marker::NDARRAY, ArrayVisitor(PhantomData))
+    }
+}
+
+/// Deserializes either form at the top level.
+struct ArrayVisitor<T, D>(PhantomData<(T, D)>);
+
+impl<T, D> ArrayVisitor<T, D> {
+    /// Visits with a fresh `Builder`, then checks its shape.
+    fn build<F, E>(visit: F) -> Result<Array<T, D>, E>
+    where
+        D: Dimension,
+        F: FnOnce(Level<T>) -> Result<(), E>,
+        E: de::Error,
+    {
+        let mut builder = Builder {
+            shape: Vec::new(),
+            leaf: None,
+            data: Vec::new(),
+            shaped: None,
+        };
+        visit(Level {
+            builder: &mut builder,
+            depth: 0,
+        })?;
+        let (shape, data) = match builder.shaped {
+            Some(shaped) => shaped,
+            None => (
+                builder
+                    .shape
+                    .into_iter()
+                    .map(Option::unwrap_or_default)
+                    .collect(),
+                builder.data,
+            ),
+        };
+        if D::NDIM.is_some_and(|ndim| ndim != shape.len()) {
+            return Err(E::custom(format!(
+                "expected a {}-dimensional array, found shape {:?}",
+                D::NDIM.unwrap_or_default(),
+                shape
+            )));
+        }
+        let len = data.len();
+        Array::from_shape_vec(IxDyn(&shape), data)
+            .and_then(|array| array.into_dimensionality::<D>())
+            .map_err(|_| E::custom(format!("shape {:?} doesn't fit {} elements", shape, len)))
+    }
+}
+
+impl<'de, T, D> Visitor<'de> for ArrayVisitor<T, D>
+where
+    T: BufferElement + de::Deserialize<'de>,
+    D: Dimension,
+{
+    type Value = Array<T, D>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("nested sequences of numbers or {:shape [..] :data [..]}")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Self::build(|level| level.visit_i64(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Self::build(|level| level.visit_u64(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Self::build(|level| level.visit_f64(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Self::build(|level| level.visit_seq(seq))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Self::build(|level| level.visit_map(map))
+    }
+
+    fn visit_newtype_struct<Z>(self, deserializer: Z) -> Result<Self::Value, Z::Error>
+    where
+        Z: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
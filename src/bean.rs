@@ -0,0 +1,617 @@
+//! Java beans and records, via reflection (see `Encoder::bean` and
+//! `Decoder::bean`).
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use jni::objects::{AutoLocal, GlobalRef, JClass, JObject, JValue};
+use jni::JNIEnv;
+
+use crate::convert::{Common, Encoder};
+use crate::{Error, Result};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// Loads `name` (e.g. `com.example.Person`) through the context class
+/// loader, which unlike FindClass also sees application classes when
+/// called from a native thread.
+pub(crate) fn load_class(env: &JNIEnv, name: &str) -> Result<GlobalRef> {
+    let thread = env.auto_local(
+        env.call_static_method(
+            "java/lang/Thread",
+            "currentThread",
+            "()Ljava/lang/Thread;",
+            &[],
+        )?
+        .l()?,
+    );
+    let mut loader = env.auto_local(
+        env.call_method(
+            thread.as_obj(),
+            "getContextClassLoader",
+            "()Ljava/lang/ClassLoader;",
+            &[],
+        )?
+        .l()?,
+    );
+    if loader.as_obj().is_null() {
+        loader = env.auto_local(
+            env.call_static_method(
+                "java/lang/ClassLoader",
+                "getSystemClassLoader",
+                "()Ljava/lang/ClassLoader;",
+                &[],
+            )?
+            .l()?,
+        );
+    }
+    let name = env.auto_local(env.new_string(name)?);
+    let class = env.auto_local(
+        env.call_static_method(
+            "java/lang/Class",
+            "forName",
+            "(Ljava/lang/String;ZLjava/lang/ClassLoader;)Ljava/lang/Class;",
+            &[name.as_obj().into(), true.into(), loader.as_obj().into()],
+        )?
+        .l()?,
+    );
+    Ok(env.new_global_ref(class.as_obj())?)
+}
+
+/// `first_name` -> `firstName`
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' && !out.is_empty() {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `firstName` -> `FirstName`
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The property name of the accessor suffix `suffix`, following
+/// `java.beans.Introspector.decapitalize`: `FirstName` -> `firstName`,
+/// but `URL` stays `URL`.
+fn decapitalize(suffix: &str) -> String {
+    let mut chars = suffix.chars();
+    match (chars.next(), chars.next()) {
+        (Some(a), Some(b)) if a.is_uppercase() && b.is_uppercase() => suffix.into(),
+        (Some(a), _) => a
+            .to_lowercase()
+            .chain(suffix[a.len_utf8()..].chars())
+            .collect(),
+        (None, _) => String::new(),
+    }
+}
+
+/// Finds the Java property for the Rust field `field`: either with
+/// exactly that name, or its camel case version.
+fn lookup<'m, T>(props: &'m HashMap<String, T>, field: &str) -> Option<&'m T> {
+    props.get(field).or_else(|| props.get(&camel_case(field)))
+}
+
+fn get_string(env: &JNIEnv, obj: JObject, method: &str) -> Result<String> {
+    let s = env.auto_local(
+        env.call_method(obj, method, "()Ljava/lang/String;", &[])?
+            .l()?,
+    );
+    Ok(env.get_string(s.as_obj().into())?.into())
+}
+
+/// The elements of the array returned by the method `method` of `obj`.
+fn get_array<'a, 'b>(
+    env: &'b JNIEnv<'a>,
+    obj: JObject<'a>,
+    method: &str,
+    sig: &str,
+) -> Result<Vec<AutoLocal<'a, 'b>>> {
+    let arr = env.auto_local(env.call_method(obj, method, sig, &[])?.l()?);
+    let arr = arr.as_obj().into_inner();
+    (0..env.get_array_length(arr)?)
+        .map(|i| Ok(env.auto_local(env.get_object_array_element(arr, i)?)))
+        .collect()
+}
+
+fn is_record(env: &JNIEnv, class: JObject) -> Result<bool> {
+    // Class.isRecord only exists since Java 16
+    if env
+        .get_method_id("java/lang/Class", "isRecord", "()Z")
+        .is_err()
+    {
+        env.exception_clear()?;
+        return Ok(false);
+    }
+    Ok(env.call_method(class, "isRecord", "()Z", &[])?.z()?)
+}
+
+/// Lets us call members of non-public record classes, where the
+/// module system allows it. This is caller sensitive, and throws
+/// without a Java frame on the stack (e.g. on a thread attached from
+/// Rust), in which case only public classes will work.
+fn set_accessible(env: &JNIEnv, member: JObject) -> Result<()> {
+    if env
+        .call_method(member, "trySetAccessible", "()Z", &[])
+        .is_err()
+    {
+        env.exception_clear()?;
+    }
+    Ok(())
+}
+
+fn public_methods<'a, 'b>(
+    env: &'b JNIEnv<'a>,
+    class: JObject<'a>,
+) -> Result<Vec<(String, i32, AutoLocal<'a, 'b>)>> {
+    get_array(env, class, "getMethods", "()[Ljava/lang/reflect/Method;")?
+        .into_iter()
+        .map(|method| {
+            let name = get_string(env, method.as_obj(), "getName")?;
+            let params = env
+                .call_method(method.as_obj(), "getParameterCount", "()I", &[])?
+                .i()?;
+            Ok((name, params, method))
+        })
+        .collect()
+}
+
+fn object_array<'a, 'b>(
+    env: &'b JNIEnv<'a>,
+    elements: &[JObject<'a>],
+) -> Result<AutoLocal<'a, 'b>> {
+    let arr = env.new_object_array(elements.len() as i32, OBJECT, JObject::null())?;
+    for (i, &elem) in elements.iter().enumerate() {
+        env.set_object_array_element(arr, i as i32, elem)?;
+    }
+    Ok(env.auto_local(JObject::from(arr)))
+}
+
+/// `Method.invoke`
+fn invoke<'a>(
+    env: &JNIEnv<'a>,
+    method: &GlobalRef,
+    obj: JObject<'a>,
+    args: &[JObject<'a>],
+) -> Result<JObject<'a>> {
+    let args = object_array(env, args)?;
+    Ok(env
+        .call_method(
+            JObject::from(method.as_obj().into_inner()),
+            "invoke",
+            "(Ljava/lang/Object;[Ljava/lang/Object;)Ljava/lang/Object;",
+            &[obj.into(), args.as_obj().into()],
+        )?
+        .l()?)
+}
+
+/// A numeric parameter type, primitive or boxed. Reflection only
+/// widens arguments, so our boxed numbers (e.g. `Long`s with
+/// `canonical_numbers`) are converted to it first.
+#[derive(Clone, Copy)]
+enum Numeric {
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl Numeric {
+    fn of(class_name: &str) -> Option<Self> {
+        match class_name {
+            "byte" | "java.lang.Byte" => Some(Numeric::Byte),
+            "short" | "java.lang.Short" => Some(Numeric::Short),
+            "int" | "java.lang.Integer" => Some(Numeric::Int),
+            "long" | "java.lang.Long" => Some(Numeric::Long),
+            "float" | "java.lang.Float" => Some(Numeric::Float),
+            "double" | "java.lang.Double" => Some(Numeric::Double),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Numeric::Byte => "byte",
+            Numeric::Short => "short",
+            Numeric::Int => "int",
+            Numeric::Long => "long",
+            Numeric::Float => "float",
+            Numeric::Double => "double",
+        }
+    }
+
+    fn boxed<'a>(self, com: &Common<'a>) -> JClass<'a> {
+        match self {
+            Numeric::Byte => com.class_byte,
+            Numeric::Short => com.class_short,
+            Numeric::Int => com.class_integer,
+            Numeric::Long => com.class_long,
+            Numeric::Float => com.class_float,
+            Numeric::Double => com.class_double,
+        }
+    }
+
+    /// `num` (a `java.lang.Number`) as this type, or `None` if it
+    /// doesn't fit.
+    fn convert<'a>(self, com: &Common<'a>, num: JObject<'a>) -> Result<Option<JValue<'a>>> {
+        let env = &com.env;
+        let double = env.call_method(num, "doubleValue", "()D", &[])?.d()?;
+        let value = match self {
+            Numeric::Float => JValue::Float(double as f32),
+            Numeric::Double => JValue::Double(double),
+            _ => {
+                let long = env.call_method(num, "longValue", "()J", &[])?.j()?;
+                // rules out fractions and BigIntegers beyond a long
+                if long as f64 != double {
+                    return Ok(None);
+                }
+                let value = match self {
+                    Numeric::Byte => i8::try_from(long).ok().map(JValue::Byte),
+                    Numeric::Short => i16::try_from(long).ok().map(JValue::Short),
+                    Numeric::Int => i32::try_from(long).ok().map(JValue::Int),
+                    _ => Some(JValue::Long(long)),
+                };
+                match value {
+                    Some(value) => value,
+                    None => return Ok(None),
+                }
+            }
+        };
+        Ok(Some(value))
+    }
+}
+
+/// The declared type of a setter or constructor parameter.
+struct Param {
+    class: GlobalRef,
+    numeric: Option<Numeric>,
+}
+
+impl Param {
+    fn new(env: &JNIEnv, class: JObject) -> Result<Self> {
+        Ok(Self {
+            numeric: Numeric::of(&get_string(env, class, "getName")?),
+            class: env.new_global_ref(class)?,
+        })
+    }
+
+    /// How well `val` suits this parameter: 2 if it can be passed as
+    /// it is, 1 if it is a number we can convert, and 0 otherwise.
+    fn fit(&self, com: &Common, val: JObject) -> Result<u8> {
+        if val.is_null() {
+            // primitives can't be null
+            let primitive = com
+                .env
+                .call_method(self.class.as_obj(), "isPrimitive", "()Z", &[])?
+                .z()?;
+            return Ok(if primitive { 0 } else { 2 });
+        }
+        Ok(match self.numeric {
+            Some(numeric) if com.is_instance_of(val, numeric.boxed(com))? => 2,
+            Some(_) if com.is_instance_of_name(val, "java/lang/Number")? => 1,
+            Some(_) => 0,
+            None if com.is_instance_of(val, JClass::from(self.class.as_obj()))? => 2,
+            None => 0,
+        })
+    }
+
+    /// `val`, converted to this parameter's type if it is a number of
+    /// another type. `None` if it doesn't fit.
+    fn coerce<'a>(&self, enc: &'a Encoder<'a>, val: JObject<'a>) -> Result<Option<JObject<'a>>> {
+        let com = &enc.com;
+        let numeric = match self.numeric {
+            Some(numeric)
+                if !com.is_instance_of(val, numeric.boxed(com))?
+                    && com.is_instance_of_name(val, "java/lang/Number")? =>
+            {
+                numeric
+            }
+            _ => return Ok(Some(com.env.new_local_ref::<JObject>(val)?)),
+        };
+        match numeric.convert(com, val)? {
+            Some(value) => Ok(Some(enc.to_boxed(value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A setter and the type it takes.
+struct Setter {
+    method: GlobalRef,
+    param: Param,
+}
+
+/// The overload of a setter whose parameter type suits `val` best.
+fn pick<'s>(com: &Common, overloads: &'s [Setter], val: JObject) -> Result<&'s Setter> {
+    let mut setter = &overloads[0];
+    if overloads.len() > 1 {
+        let mut best = 0;
+        for overload in overloads {
+            let fit = overload.param.fit(com, val)?;
+            if fit > best {
+                setter = overload;
+                best = fit;
+            }
+        }
+    }
+    Ok(setter)
+}
+
+/// How to construct an instance of a class from Rust struct fields.
+pub(crate) struct Builder {
+    class_name: String,
+    ctor: GlobalRef,
+    kind: BuilderKind,
+}
+
+enum BuilderKind {
+    /// The canonical constructor, taking the components (and their
+    /// types) in order.
+    Record(Vec<(String, Param)>),
+    /// The no-argument constructor, then property -> setters, one per
+    /// overload
+    Bean(HashMap<String, Vec<Setter>>),
+}
+
+impl Builder {
+    pub(crate) fn new(env: &JNIEnv, class: &GlobalRef, class_name: &str) -> Result<Self> {
+        let class = class.as_obj();
+        if is_record(env, class)? {
+            let comps = get_array(
+                env,
+                class,
+                "getRecordComponents",
+                "()[Ljava/lang/reflect/RecordComponent;",
+            )?;
+            let types = env.auto_local(JObject::from(env.new_object_array(
+                comps.len() as i32,
+                "java/lang/Class",
+                JObject::null(),
+            )?));
+            let mut params = Vec::with_capacity(comps.len());
+            for (i, comp) in comps.iter().enumerate() {
+                let ty = env.auto_local(
+                    env.call_method(comp.as_obj(), "getType", "()Ljava/lang/Class;", &[])?
+                        .l()?,
+                );
+                env.set_object_array_element(types.as_obj().into_inner(), i as i32, ty.as_obj())?;
+                params.push((
+                    get_string(env, comp.as_obj(), "getName")?,
+                    Param::new(env, ty.as_obj())?,
+                ));
+            }
+            let ctor = env.auto_local(
+                env.call_method(
+                    class,
+                    "getDeclaredConstructor",
+                    "([Ljava/lang/Class;)Ljava/lang/reflect/Constructor;",
+                    &[types.as_obj().into()],
+                )?
+                .l()?,
+            );
+            set_accessible(env, ctor.as_obj())?;
+            return Ok(Self {
+                class_name: class_name.into(),
+                ctor: env.new_global_ref(ctor.as_obj())?,
+                kind: BuilderKind::Record(params),
+            });
+        }
+
+        let no_args = env.auto_local(JObject::from(env.new_object_array(
+            0,
+            "java/lang/Class",
+            JObject::null(),
+        )?));
+        let ctor = env.call_method(
+            class,
+            "getConstructor",
+            "([Ljava/lang/Class;)Ljava/lang/reflect/Constructor;",
+            &[no_args.as_obj().into()],
+        );
+        let ctor = match ctor {
+            Ok(ctor) => env.auto_local(ctor.l()?),
+            Err(_) => {
+                env.exception_clear()?;
+                return Err(Error::Message(format!(
+                    "{} has no public no-argument constructor",
+                    class_name
+                )));
+            }
+        };
+        let mut setters: HashMap<_, Vec<_>> = HashMap::new();
+        for (name, params, method) in public_methods(env, class)? {
+            if params == 1 && name.len() > 3 && name.starts_with("set") {
+                let types = get_array(
+                    env,
+                    method.as_obj(),
+                    "getParameterTypes",
+                    "()[Ljava/lang/Class;",
+                )?;
+                setters
+                    .entry(decapitalize(&name[3..]))
+                    .or_default()
+                    .push(Setter {
+                        method: env.new_global_ref(method.as_obj())?,
+                        param: Param::new(env, types[0].as_obj())?,
+                    });
+            }
+        }
+        Ok(Self {
+            class_name: class_name.into(),
+            ctor: env.new_global_ref(ctor.as_obj())?,
+            kind: BuilderKind::Bean(setters),
+        })
+    }
+
+    /// Creates an instance from the Rust field names and their values.
+    /// Record components without a field are `null`. Numbers are
+    /// converted to the parameter types, and of overloaded setters the
+    /// one whose type suits the value is called.
+    pub(crate) fn build<'a>(
+        &self,
+        enc: &'a Encoder<'a>,
+        fields: &[(String, AutoLocal<'a, '_>)],
+    ) -> Result<JObject<'a>> {
+        let env = &enc.com.env;
+        let coerce = |param: &Param, field: &str, val: JObject<'a>| match param.coerce(enc, val)? {
+            Some(arg) => Ok(env.auto_local(arg)),
+            None => Err(Error::Message(format!(
+                "field `{}` doesn't fit in the {} parameter of {}",
+                field,
+                param.numeric.map_or("", Numeric::name),
+                self.class_name
+            ))),
+        };
+        let new_instance = |args: &[JObject<'a>]| -> Result<JObject<'a>> {
+            let args = object_array(env, args)?;
+            Ok(env
+                .call_method(
+                    JObject::from(self.ctor.as_obj().into_inner()),
+                    "newInstance",
+                    "([Ljava/lang/Object;)Ljava/lang/Object;",
+                    &[args.as_obj().into()],
+                )?
+                .l()?)
+        };
+        match &self.kind {
+            BuilderKind::Record(comps) => {
+                let mut args = vec![JObject::null(); comps.len()];
+                let mut coerced = Vec::with_capacity(fields.len());
+                for (field, val) in fields {
+                    let camel = camel_case(field);
+                    let i = comps
+                        .iter()
+                        .position(|(c, _)| c == field || *c == camel)
+                        .ok_or_else(|| {
+                            Error::Message(format!(
+                                "record {} has no component `{}` for field `{}`",
+                                self.class_name, camel, field
+                            ))
+                        })?;
+                    let arg = coerce(&comps[i].1, field, val.as_obj())?;
+                    args[i] = arg.as_obj();
+                    coerced.push(arg);
+                }
+                new_instance(&args)
+            }
+            BuilderKind::Bean(setters) => {
+                let obj = new_instance(&[])?;
+                for (field, val) in fields {
+                    let overloads = lookup(setters, field).ok_or_else(|| {
+                        Error::Message(format!(
+                            "{} has no setter set{}() for field `{}`",
+                            self.class_name,
+                            capitalize(&camel_case(field)),
+                            field
+                        ))
+                    })?;
+                    let setter = pick(&enc.com, overloads, val.as_obj())?;
+                    let arg = coerce(&setter.param, field, val.as_obj())?;
+                    env.auto_local(invoke(env, &setter.method, obj, &[arg.as_obj()])?);
+                }
+                Ok(obj)
+            }
+        }
+    }
+}
+
+/// How to read Rust struct fields out of an instance of a class.
+pub(crate) struct Accessors {
+    pub(crate) class: GlobalRef,
+    class_name: String,
+    record: bool,
+    /// property -> getter (or record accessor)
+    getters: HashMap<String, GlobalRef>,
+}
+
+impl Accessors {
+    pub(crate) fn new(env: &JNIEnv, class: GlobalRef, class_name: &str) -> Result<Self> {
+        let mut getters = HashMap::new();
+        let record = is_record(env, class.as_obj())?;
+        if record {
+            let comps = get_array(
+                env,
+                class.as_obj(),
+                "getRecordComponents",
+                "()[Ljava/lang/reflect/RecordComponent;",
+            )?;
+            for comp in comps {
+                let name = get_string(env, comp.as_obj(), "getName")?;
+                let accessor = env.auto_local(
+                    env.call_method(
+                        comp.as_obj(),
+                        "getAccessor",
+                        "()Ljava/lang/reflect/Method;",
+                        &[],
+                    )?
+                    .l()?,
+                );
+                set_accessible(env, accessor.as_obj())?;
+                getters.insert(name, env.new_global_ref(accessor.as_obj())?);
+            }
+        } else {
+            for (name, params, method) in public_methods(env, class.as_obj())? {
+                let prop = if params != 0 || name == "getClass" {
+                    continue;
+                } else if name.len() > 3 && name.starts_with("get") {
+                    decapitalize(&name[3..])
+                } else if name.len() > 2 && name.starts_with("is") {
+                    decapitalize(&name[2..])
+                } else {
+                    continue;
+                };
+                getters.insert(prop, env.new_global_ref(method.as_obj())?);
+            }
+        }
+        Ok(Self {
+            class,
+            class_name: class_name.into(),
+            record,
+            getters,
+        })
+    }
+
+    /// Reads `fields` (Rust field names) out of `obj` using their
+    /// getters.
+    pub(crate) fn read<'a, 'b>(
+        &self,
+        env: &'b JNIEnv<'a>,
+        obj: JObject<'a>,
+        fields: &[&'static str],
+    ) -> Result<Vec<(&'static str, AutoLocal<'a, 'b>)>> {
+        fields
+            .iter()
+            .map(|&field| {
+                let getter = lookup(&self.getters, field).ok_or_else(|| {
+                    let camel = camel_case(field);
+                    let expected = if self.record {
+                        format!("{}()", camel)
+                    } else {
+                        let cap = capitalize(&camel);
+                        format!("get{}() or is{}()", cap, cap)
+                    };
+                    Error::Message(format!(
+                        "{} has no accessor {} for field `{}`",
+                        self.class_name, expected, field
+                    ))
+                })?;
+                Ok((field, env.auto_local(invoke(env, getter, obj, &[])?)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,511 @@
+//! Zero-copy export of Rust buffers as direct `java.nio` buffers, and
+//! copying export as Java primitive arrays.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+use jni::objects::{JClass, JObject};
+use jni::sys::jsize;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::convert::{Decoder, Encoder};
+use crate::marker;
+use crate::native;
+use crate::Result;
+
+mod private {
+    /// The `java.nio` buffer type corresponding to a Rust element type.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Kind {
+        Byte,
+        Short,
+        Int,
+        Long,
+        Float,
+        Double,
+    }
+
+    pub trait Sealed {
+        const KIND: Kind;
+    }
+}
+
+pub(crate) use private::Kind;
+
+impl Kind {
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Byte => "java/nio/ByteBuffer",
+            Kind::Short => "java/nio/ShortBuffer",
+            Kind::Int => "java/nio/IntBuffer",
+            Kind::Long => "java/nio/LongBuffer",
+            Kind::Float => "java/nio/FloatBuffer",
+            Kind::Double => "java/nio/DoubleBuffer",
+        }
+    }
+
+    /// the `ByteBuffer` method returning a view of this type
+    fn view(self) -> Option<&'static str> {
+        match self {
+            Kind::Byte => None,
+            Kind::Short => Some("asShortBuffer"),
+            Kind::Int => Some("asIntBuffer"),
+            Kind::Long => Some("asLongBuffer"),
+            Kind::Float => Some("asFloatBuffer"),
+            Kind::Double => Some("asDoubleBuffer"),
+        }
+    }
+
+    /// the JNI name of the primitive array class, e.g. `[D`
+    fn array_class(self) -> &'static str {
+        match self {
+            Kind::Byte => "[B",
+            Kind::Short => "[S",
+            Kind::Int => "[I",
+            Kind::Long => "[J",
+            Kind::Float => "[F",
+            Kind::Double => "[D",
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Kind::Byte => 1,
+            Kind::Short => 2,
+            Kind::Int | Kind::Float => 4,
+            Kind::Long | Kind::Double => 8,
+        }
+    }
+}
+
+/// Element types which can be shared with the JVM as a direct
+/// `java.nio` buffer.
+pub trait BufferElement: private::Sealed + Copy + Send + Sync + 'static {}
+
+macro_rules! element {
+    ($type:ty, $kind:ident) => {
+        impl private::Sealed for $type {
+            const KIND: Kind = Kind::$kind;
+        }
+        impl BufferElement for $type {}
+    };
+}
+
+element!(u8, Byte);
+element!(i8, Byte);
+element!(i16, Short);
+element!(i32, Int);
+element!(i64, Long);
+element!(f32, Float);
+element!(f64, Double);
+
+/// A buffer which serializes to a read-only direct `java.nio` buffer
+/// (`ByteBuffer` for `u8`/`i8`, `DoubleBuffer` for `f64`, etc.)
+/// pointing directly at Rust memory, without copying.
+///
+/// The JVM keeps a reference to the data until the buffer is garbage
+/// collected, at which point it is released using a
+/// `java.lang.ref.Cleaner` (this needs the `serde_clj.NativeRelease`
+/// class on the classpath). Typed views use the platform's native
+/// byte order.
+///
+/// Deserializes from a buffer of the matching type (direct or not),
+/// copying its remaining elements. Other serializers see a plain
+/// sequence.
+#[derive(Clone)]
+pub struct DirectBuffer<T: BufferElement = u8> {
+    data: Arc<Vec<T>>,
+}
+
+impl<T: BufferElement> DirectBuffer<T> {
+    pub fn new(data: Vec<T>) -> Self {
+        Self {
+            data: Arc::new(data),
+        }
+    }
+}
+
+impl<T: BufferElement> From<Vec<T>> for DirectBuffer<T> {
+    fn from(data: Vec<T>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl<T: BufferElement> Deref for DirectBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T: BufferElement + fmt::Debug> fmt::Debug for DirectBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DirectBuffer").field(&self.data).finish()
+    }
+}
+
+/// What `DirectBuffer` stashes for the `Serializer`.
+pub(crate) struct Export {
+    owner: native::Owned,
+    data: *const u8,
+    len: usize,
+    kind: Kind,
+}
+
+impl<T: BufferElement + Serialize> Serialize for DirectBuffer<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash(Export {
+            owner: Box::new(self.data.clone()),
+            data: self.data.as_ptr() as *const u8,
+            len: self.data.len() * mem::size_of::<T>(),
+            kind: T::KIND,
+        });
+        serializer.serialize_newtype_struct(marker::DIRECT_BUFFER, &self.data[..])
+    }
+}
+
+impl<'de, T: BufferElement + Deserialize<'de>> Deserialize<'de> for DirectBuffer<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BufferVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: BufferElement + Deserialize<'de>> Visitor<'de> for BufferVisitor<T> {
+            type Value = DirectBuffer<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a direct java.nio buffer")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Self::Value, E> {
+                Ok(DirectBuffer::new(from_bytes(v)))
+            }
+
+            fn visit_newtype_struct<D>(self, d: D) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                Vec::deserialize(d).map(DirectBuffer::new)
+            }
+        }
+
+        marker::stash(T::KIND);
+        deserializer.deserialize_newtype_struct(marker::DIRECT_BUFFER, BufferVisitor(PhantomData))
+    }
+}
+
+/// A `Vec` which serializes to a Java primitive array (`byte[]` for
+/// `u8`/`i8`, `double[]` for `f64`, etc.) by copying. Deserializes
+/// from a primitive array of the matching type, or from any sequence
+/// of numbers. Other serializers see a plain sequence.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrimitiveArray<T: BufferElement = f64>(pub Vec<T>);
+
+impl<T: BufferElement> From<Vec<T>> for PrimitiveArray<T> {
+    fn from(data: Vec<T>) -> Self {
+        PrimitiveArray(data)
+    }
+}
+
+impl<T: BufferElement> Deref for PrimitiveArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+/// What `PrimitiveArray` stashes for the `Serializer`: the elements in
+/// native byte order.
+pub(crate) struct ArrayExport {
+    data: Vec<u8>,
+    kind: Kind,
+}
+
+impl ArrayExport {
+    pub(crate) fn new<T: BufferElement>(data: &[T]) -> Self {
+        ArrayExport {
+            data: as_bytes(data).to_vec(),
+            kind: T::KIND,
+        }
+    }
+}
+
+impl<T: BufferElement + Serialize> Serialize for PrimitiveArray<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash(ArrayExport::new(&self.0));
+        serializer.serialize_newtype_struct(marker::PRIMITIVE_ARRAY, &self.0)
+    }
+}
+
+impl<'de, T: BufferElement + Deserialize<'de>> Deserialize<'de> for PrimitiveArray<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ArrayVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: BufferElement + Deserialize<'de>> Visitor<'de> for ArrayVisitor<T> {
+            type Value = PrimitiveArray<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a primitive array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Self::Value, E> {
+                Ok(PrimitiveArray(from_bytes(v)))
+            }
+
+            fn visit_newtype_struct<D>(self, d: D) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                Vec::deserialize(d).map(PrimitiveArray)
+            }
+        }
+
+        marker::stash(T::KIND);
+        deserializer.deserialize_newtype_struct(marker::PRIMITIVE_ARRAY, ArrayVisitor(PhantomData))
+    }
+}
+
+fn as_bytes<T: BufferElement>(data: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) }
+}
+
+/// Copies native-order `bytes` into a `Vec` (`bytes` may not be
+/// aligned for `T`).
+pub(crate) fn from_bytes<T: BufferElement>(bytes: &[u8]) -> Vec<T> {
+    let len = bytes.len() / mem::size_of::<T>();
+    let mut data = Vec::<T>::with_capacity(len);
+    // every bit pattern is valid for the (sealed) element types
+    unsafe {
+        ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            data.as_mut_ptr() as *mut u8,
+            len * mem::size_of::<T>(),
+        );
+        data.set_len(len);
+    }
+    data
+}
+
+/// Builds the Java primitive array for `export`.
+pub(crate) fn array_to_java<'a>(enc: &'a Encoder<'a>, export: ArrayExport) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let len = (export.data.len() / export.kind.size()) as jsize;
+    let arr = match export.kind {
+        Kind::Byte => {
+            let arr = env.new_byte_array(len)?;
+            env.set_byte_array_region(arr, 0, &from_bytes::<i8>(&export.data))?;
+            arr
+        }
+        Kind::Short => {
+            let arr = env.new_short_array(len)?;
+            env.set_short_array_region(arr, 0, &from_bytes::<i16>(&export.data))?;
+            arr
+        }
+        Kind::Int => {
+            let arr = env.new_int_array(len)?;
+            env.set_int_array_region(arr, 0, &from_bytes::<i32>(&export.data))?;
+            arr
+        }
+        Kind::Long => {
+            let arr = env.new_long_array(len)?;
+            env.set_long_array_region(arr, 0, &from_bytes::<i64>(&export.data))?;
+            arr
+        }
+        Kind::Float => {
+            let arr = env.new_float_array(len)?;
+            env.set_float_array_region(arr, 0, &from_bytes::<f32>(&export.data))?;
+            arr
+        }
+        Kind::Double => {
+            let arr = env.new_double_array(len)?;
+            env.set_double_array_region(arr, 0, &from_bytes::<f64>(&export.data))?;
+            arr
+        }
+    };
+    Ok(JObject::from(arr))
+}
+
+/// Returns the contents of `obj` in native byte order if it is a
+/// primitive array of type `kind`.
+pub(crate) fn array_from_java(dec: &Decoder, obj: JObject, kind: Kind) -> Result<Option<Vec<u8>>> {
+    let env = &dec.com.env;
+    if !dec.com.is_instance_of_name(obj, kind.array_class())? {
+        return Ok(None);
+    }
+    let arr = obj.into_inner();
+    let len = env.get_array_length(arr)? as usize;
+    let data = match kind {
+        Kind::Byte => {
+            let mut buf = vec![0; len];
+            env.get_byte_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+        Kind::Short => {
+            let mut buf = vec![0; len];
+            env.get_short_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+        Kind::Int => {
+            let mut buf = vec![0; len];
+            env.get_int_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+        Kind::Long => {
+            let mut buf = vec![0; len];
+            env.get_long_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+        Kind::Float => {
+            let mut buf = vec![0.0; len];
+            env.get_float_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+        Kind::Double => {
+            let mut buf = vec![0.0; len];
+            env.get_double_array_region(arr, 0, &mut buf)?;
+            as_bytes(&buf).to_vec()
+        }
+    };
+    Ok(Some(data))
+}
+
+/// Builds the read-only direct buffer for `export`, handing ownership
+/// of the underlying memory to the JVM.
+pub(crate) fn to_java<'a>(enc: &'a Encoder<'a>, export: Export) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let class = export.kind.class();
+    let sig = format!("()L{};", class);
+
+    let bytes = env.auto_local(native::new_direct_byte_buffer(
+        env,
+        export.data,
+        export.len,
+    )?);
+    let typed = match export.kind.view() {
+        None => None,
+        Some(view) => {
+            let class = enc.com.class("java/nio/ByteOrder")?;
+            let order = env.auto_local(
+                env.call_static_method(
+                    JClass::from(class.as_obj()),
+                    "nativeOrder",
+                    "()Ljava/nio/ByteOrder;",
+                    &[],
+                )?
+                .l()?,
+            );
+            let ordered = env.auto_local(
+                env.call_method(
+                    bytes.as_obj(),
+                    "order",
+                    "(Ljava/nio/ByteOrder;)Ljava/nio/ByteBuffer;",
+                    &[order.as_obj().into()],
+                )?
+                .l()?,
+            );
+            Some(env.auto_local(env.call_method(ordered.as_obj(), view, &sig, &[])?.l()?))
+        }
+    };
+    let writable = typed.as_ref().unwrap_or(&bytes);
+    let res = env
+        .call_method(writable.as_obj(), "asReadOnlyBuffer", &sig, &[])?
+        .l()?;
+    native::attach_cleaner(&enc.com, res, export.owner)?;
+    Ok(res)
+}
+
+/// Returns the remaining contents of `obj` (from its `position()` to
+/// its `limit()`) if it is a buffer of type `kind`, in native byte
+/// order. A direct buffer in native order is borrowed, so the data is
+/// only valid while a reference to `obj` is held: the caller must keep
+/// one for `'de` if it lends the data out. Other buffers are copied.
+pub(crate) fn from_java<'de>(
+    dec: &'de Decoder<'de>,
+    obj: JObject<'de>,
+    kind: Kind,
+) -> Result<Option<Cow<'de, [u8]>>> {
+    if !dec.com.is_instance_of_name(obj, kind.class())? {
+        return Ok(None);
+    }
+    let env = &dec.com.env;
+    let position = env.call_method(obj, "position", "()I", &[])?.i()? as usize;
+    let remaining = env.call_method(obj, "remaining", "()I", &[])?.i()? as usize;
+    let data = match native::direct_buffer_region(env, obj) {
+        Some((data, _)) => data,
+        None => return Ok(Some(Cow::Owned(copy_remaining(dec, obj, kind, remaining)?))),
+    };
+    let bytes =
+        unsafe { slice::from_raw_parts(data.add(position * kind.size()), remaining * kind.size()) };
+    if kind.size() == 1 || is_native_order(dec, obj)? {
+        Ok(Some(Cow::Borrowed(bytes)))
+    } else {
+        let mut swapped = bytes.to_vec();
+        for elem in swapped.chunks_mut(kind.size()) {
+            elem.reverse();
+        }
+        Ok(Some(Cow::Owned(swapped)))
+    }
+}
+
+/// Copies the `len` remaining elements of a (non-direct) buffer
+/// through a primitive array.
+fn copy_remaining(dec: &Decoder, obj: JObject, kind: Kind, len: usize) -> Result<Vec<u8>> {
+    let env = &dec.com.env;
+    let len = len as jsize;
+    let arr = match kind {
+        Kind::Byte => env.new_byte_array(len)?,
+        Kind::Short => env.new_short_array(len)?,
+        Kind::Int => env.new_int_array(len)?,
+        Kind::Long => env.new_long_array(len)?,
+        Kind::Float => env.new_float_array(len)?,
+        Kind::Double => env.new_double_array(len)?,
+    };
+    let arr = env.auto_local(JObject::from(arr));
+    // `get` moves the position, so read through a duplicate
+    let buf_sig = format!("()L{};", kind.class());
+    let dup = env.auto_local(env.call_method(obj, "duplicate", &buf_sig, &[])?.l()?);
+    let get_sig = format!("({})L{};", kind.array_class(), kind.class());
+    env.auto_local(
+        env.call_method(dup.as_obj(), "get", &get_sig, &[arr.as_obj().into()])?
+            .l()?,
+    );
+    Ok(array_from_java(dec, arr.as_obj(), kind)?.unwrap_or_default())
+}
+
+fn is_native_order(dec: &Decoder, obj: JObject) -> Result<bool> {
+    let env = &dec.com.env;
+    let class = dec.com.class("java/nio/ByteOrder")?;
+    let native = env.auto_local(
+        env.call_static_method(
+            JClass::from(class.as_obj()),
+            "nativeOrder",
+            "()Ljava/nio/ByteOrder;",
+            &[],
+        )?
+        .l()?,
+    );
+    let order = env.auto_local(
+        env.call_method(obj, "order", "()Ljava/nio/ByteOrder;", &[])?
+            .l()?,
+    );
+    Ok(env.is_same_object(native.as_obj(), order.as_obj())?)
+}
@@ -0,0 +1,58 @@
+//! Modules for use with `#[serde(with = "...")]` which choose the
+//! Clojure collection a field is serialized as. Without them, all
+//! sequences become vectors and all maps become hash maps.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use std::collections::{BTreeMap, HashSet};
+//! #[derive(Serialize, Deserialize)]
+//! struct Doc {
+//!     #[serde(with = "serde_clj::as_set")]
+//!     tags: HashSet<String>,
+//!     #[serde(with = "serde_clj::as_sorted_map")]
+//!     counts: BTreeMap<String, i64>,
+//! }
+//! ```
+//!
+//! Deserialization accepts any collection, as usual. Other serializers
+//! see an ordinary newtype around the value.
+
+macro_rules! with_module {
+    ($name:ident, $marker:ident, $doc:literal) => {
+        #[doc = $doc]
+        pub mod $name {
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            use crate::marker;
+
+            pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                T: ?Sized + Serialize,
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct(marker::$marker, value)
+            }
+
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+            where
+                T: Deserialize<'de>,
+                D: Deserializer<'de>,
+            {
+                T::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+with_module!(as_list, LIST, "Serializes a sequence as a list.");
+with_module!(as_set, SET, "Serializes a sequence as a hash set.");
+with_module!(
+    as_sorted_set,
+    SORTED_SET,
+    "Serializes a sequence as a sorted set."
+);
+with_module!(
+    as_sorted_map,
+    SORTED_MAP,
+    "Serializes a map as a sorted map (keys must be `Comparable`)."
+);
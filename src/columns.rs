@@ -0,0 +1,850 @@
+//! Columnar (de)serialization of sequences of structs, e.g. for
+//! `tech.ml.dataset`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use jni::objects::{AutoLocal, JClass, JObject, JValue};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::buffer::{self, ArrayExport, Kind};
+use crate::convert::{ArrayList, Decoder, Encoder};
+use crate::de::{from_object, Deserializer};
+use crate::ser::to_object;
+use crate::{Error, Raw, Result};
+
+/// Serializes `rows`, which must be structs, as a map from field name
+/// to column: `{:a long[], :b String[], ...}`, much more compactly
+/// than a vector of maps.
+///
+/// Integer, float and boolean fields become primitive arrays of the
+/// type the `Encoder` would box them as (`long[]` for `i64`, `short[]`
+/// for `u8`, all `long[]`/`double[]` with `canonical_numbers`),
+/// strings and chars `String[]`, and anything else an `Object[]` of
+/// whatever `to_object` produces. A column which can't hold one of its
+/// values (e.g. an untagged enum) falls back to `Object[]`.
+///
+/// `nil`s (`None`, or a field skipped with `skip_serializing_if`)
+/// become `null` in object columns. In primitive columns they are `0`
+/// or `false`, and the map's metadata has a mask for the column under
+/// `:missing`, e.g. `{:missing {:a boolean[]}}` where `true` marks a
+/// `nil`. For plain Java, which has no metadata, such columns are
+/// `Object[]`s instead.
+pub fn to_columns<'a, T: Serialize>(enc: &'a Encoder<'a>, rows: &[T]) -> Result<JObject<'a>> {
+    let mut cols = Columns {
+        enc,
+        cols: Vec::new(),
+        rows: 0,
+        next: 0,
+    };
+    for row in rows {
+        row.serialize(RowSerializer(&mut cols))?;
+        cols.end_row()?;
+    }
+    cols.into_java()
+}
+
+/// A scalar field value.
+enum Cell {
+    Null,
+    Bool(bool),
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Str(String),
+}
+
+/// The values of a column so far.
+enum Values<'a> {
+    /// only `nil`s
+    Nulls,
+    Bool(Vec<bool>),
+    Byte(Vec<i8>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    Str(Vec<Option<String>>),
+    /// anything else
+    Objects(ArrayList<'a>),
+}
+
+impl<'a> Values<'a> {
+    /// A column for `cell`s, after `len` `nil`s.
+    fn starting_with(cell: &Cell, len: usize) -> Self {
+        match cell {
+            Cell::Null => Values::Nulls,
+            Cell::Bool(_) => Values::Bool(vec![false; len]),
+            Cell::Byte(_) => Values::Byte(vec![0; len]),
+            Cell::Short(_) => Values::Short(vec![0; len]),
+            Cell::Int(_) => Values::Int(vec![0; len]),
+            Cell::Long(_) => Values::Long(vec![0; len]),
+            Cell::Float(_) => Values::Float(vec![0.0; len]),
+            Cell::Double(_) => Values::Double(vec![0.0; len]),
+            Cell::Str(_) => Values::Str(vec![None; len]),
+        }
+    }
+
+    /// Appends `cell`, or gives it back if it doesn't fit.
+    fn push(&mut self, enc: &'a Encoder<'a>, cell: Cell) -> Result<Option<Cell>> {
+        match (self, cell) {
+            (Values::Nulls, Cell::Null) => {}
+            (Values::Bool(v), Cell::Bool(x)) => v.push(x),
+            (Values::Bool(v), Cell::Null) => v.push(false),
+            (Values::Byte(v), Cell::Byte(x)) => v.push(x),
+            (Values::Byte(v), Cell::Null) => v.push(0),
+            (Values::Short(v), Cell::Short(x)) => v.push(x),
+            (Values::Short(v), Cell::Null) => v.push(0),
+            (Values::Int(v), Cell::Int(x)) => v.push(x),
+            (Values::Int(v), Cell::Null) => v.push(0),
+            (Values::Long(v), Cell::Long(x)) => v.push(x),
+            (Values::Long(v), Cell::Null) => v.push(0),
+            (Values::Float(v), Cell::Float(x)) => v.push(x),
+            (Values::Float(v), Cell::Null) => v.push(0.0),
+            (Values::Double(v), Cell::Double(x)) => v.push(x),
+            (Values::Double(v), Cell::Null) => v.push(0.0),
+            (Values::Str(v), Cell::Str(x)) => v.push(Some(x)),
+            (Values::Str(v), Cell::Null) => v.push(None),
+            (Values::Objects(list), cell) => list.add(cell.to_java(enc)?)?,
+            (_, cell) => return Ok(Some(cell)),
+        }
+        Ok(None)
+    }
+
+    /// The `i`th value, boxed.
+    fn boxed(&self, enc: &'a Encoder<'a>, i: usize) -> Result<JObject<'a>> {
+        let val = match self {
+            Values::Nulls | Values::Objects(_) => return Ok(JObject::null()),
+            Values::Bool(v) => JValue::Bool(v[i].into()),
+            Values::Byte(v) => JValue::Byte(v[i]),
+            Values::Short(v) => JValue::Short(v[i]),
+            Values::Int(v) => JValue::Int(v[i]),
+            Values::Long(v) => JValue::Long(v[i]),
+            Values::Float(v) => JValue::Float(v[i]),
+            Values::Double(v) => JValue::Double(v[i]),
+            Values::Str(v) => {
+                return match &v[i] {
+                    Some(s) => Ok(enc.com.env.new_string(s)?.into()),
+                    None => Ok(JObject::null()),
+                }
+            }
+        };
+        enc.to_boxed(val)
+    }
+}
+
+impl Cell {
+    fn to_java<'a>(&self, enc: &'a Encoder<'a>) -> Result<JObject<'a>> {
+        let val = match *self {
+            Cell::Null => return Ok(JObject::null()),
+            Cell::Bool(x) => JValue::Bool(x.into()),
+            Cell::Byte(x) => JValue::Byte(x),
+            Cell::Short(x) => JValue::Short(x),
+            Cell::Int(x) => JValue::Int(x),
+            Cell::Long(x) => JValue::Long(x),
+            Cell::Float(x) => JValue::Float(x),
+            Cell::Double(x) => JValue::Double(x),
+            Cell::Str(ref s) => return Ok(enc.com.env.new_string(s)?.into()),
+        };
+        enc.to_boxed(val)
+    }
+}
+
+struct Column<'a> {
+    name: &'static str,
+    values: Values<'a>,
+    /// the rows which are `nil`, in order
+    missing: Vec<usize>,
+    len: usize,
+}
+
+impl<'a> Column<'a> {
+    fn push(&mut self, enc: &'a Encoder<'a>, cell: Cell) -> Result<()> {
+        if let Cell::Null = cell {
+            self.missing.push(self.len);
+        } else if let Values::Nulls = self.values {
+            self.values = Values::starting_with(&cell, self.len);
+        }
+        if let Some(cell) = self.values.push(enc, cell)? {
+            self.values = Values::Objects(self.to_objects(enc)?);
+            self.values.push(enc, cell)?;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// This method will invalidate the local ref `obj`!
+    fn push_object(&mut self, enc: &'a Encoder<'a>, obj: JObject<'a>) -> Result<()> {
+        if let Values::Objects(list) = &self.values {
+            list.add(obj)?;
+        } else {
+            let list = self.to_objects(enc)?;
+            list.add(obj)?;
+            self.values = Values::Objects(list);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Boxes the values so far.
+    fn to_objects(&self, enc: &'a Encoder<'a>) -> Result<ArrayList<'a>> {
+        let list = ArrayList::new(enc)?;
+        for i in 0..self.len {
+            if self.missing.binary_search(&i).is_ok() {
+                list.add(JObject::null())?;
+            } else {
+                list.add(self.values.boxed(enc, i)?)?;
+            }
+        }
+        Ok(list)
+    }
+
+    /// The column's array, and its `nil` mask if it needs one.
+    fn into_java(self, enc: &'a Encoder<'a>) -> Result<(JObject<'a>, Option<JObject<'a>>)> {
+        let env = &enc.com.env;
+        let len = self.len as i32;
+        let primitive = !matches!(
+            self.values,
+            Values::Nulls | Values::Str(_) | Values::Objects(_)
+        );
+        if primitive && !self.missing.is_empty() && !enc.is_clojure() {
+            return Ok((self.to_objects(enc)?.into_array()?, None));
+        }
+        let mask = if primitive && !self.missing.is_empty() {
+            let mut mask = vec![0; self.len];
+            for &i in &self.missing {
+                mask[i] = 1;
+            }
+            let arr = env.new_boolean_array(len)?;
+            env.set_boolean_array_region(arr, 0, &mask)?;
+            Some(JObject::from(arr))
+        } else {
+            None
+        };
+        let arr = match self.values {
+            Values::Nulls => {
+                let class = env.auto_local(env.find_class("java/lang/Object")?);
+                let class = JClass::from(class.as_obj());
+                JObject::from(env.new_object_array(len, class, JObject::null())?)
+            }
+            Values::Bool(v) => {
+                let arr = env.new_boolean_array(len)?;
+                let v: Vec<u8> = v.into_iter().map(u8::from).collect();
+                env.set_boolean_array_region(arr, 0, &v)?;
+                JObject::from(arr)
+            }
+            Values::Byte(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Short(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Int(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Long(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Float(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Double(v) => buffer::array_to_java(enc, ArrayExport::new(&v))?,
+            Values::Str(v) => {
+                let arr = env.new_object_array(len, enc.com.class_string, JObject::null())?;
+                for (i, s) in v.iter().enumerate() {
+                    if let Some(s) = s {
+                        let s = env.auto_local(JObject::from(env.new_string(s)?));
+                        env.set_object_array_element(arr, i as i32, s.as_obj())?;
+                    }
+                }
+                JObject::from(arr)
+            }
+            Values::Objects(list) => list.into_array()?,
+        };
+        Ok((arr, mask))
+    }
+}
+
+struct Columns<'a> {
+    enc: &'a Encoder<'a>,
+    cols: Vec<Column<'a>>,
+    rows: usize,
+    /// where the next field probably is
+    next: usize,
+}
+
+impl<'a> Columns<'a> {
+    fn push_field<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result<()> {
+        let enc = self.enc;
+        let i = match self.cols.get(self.next) {
+            Some(col) if col.name == name => self.next,
+            _ => match self.cols.iter().position(|col| col.name == name) {
+                Some(i) => i,
+                None => {
+                    // `nil` in the rows so far
+                    self.cols.push(Column {
+                        name,
+                        values: Values::Nulls,
+                        missing: (0..self.rows).collect(),
+                        len: self.rows,
+                    });
+                    self.cols.len() - 1
+                }
+            },
+        };
+        self.next = i + 1;
+        let col = &mut self.cols[i];
+        if col.len > self.rows {
+            return Err(Error::Message(format!("duplicate field `{}`", name)));
+        }
+        match value.serialize(CellSerializer { enc }) {
+            Ok(cell) => col.push(enc, cell),
+            Err(CellError::Compound) => col.push_object(enc, to_object(enc, &value)?),
+            Err(CellError::Error(e)) => Err(e),
+        }
+    }
+
+    fn end_row(&mut self) -> Result<()> {
+        self.rows += 1;
+        self.next = 0;
+        for col in &mut self.cols {
+            if col.len < self.rows {
+                col.push(self.enc, Cell::Null)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn into_java(self) -> Result<JObject<'a>> {
+        let enc = self.enc;
+        let map = ArrayList::new(enc)?;
+        let masks = ArrayList::new(enc)?;
+        let mut any_masks = false;
+        for col in self.cols {
+            let name = col.name;
+            let (arr, mask) = col.into_java(enc)?;
+            map.add(enc.get_keyword(name)?)?;
+            map.add(arr)?;
+            if let Some(mask) = mask {
+                masks.add(enc.get_keyword(name)?)?;
+                masks.add(mask)?;
+                any_masks = true;
+            }
+        }
+        let map = map.into_hashmap()?;
+        if !any_masks {
+            return Ok(map);
+        }
+        let meta = ArrayList::new(enc)?;
+        meta.add(enc.get_keyword("missing")?)?;
+        meta.add(masks.into_hashmap()?)?;
+        enc.with_meta(map, meta.into_hashmap()?)
+    }
+}
+
+fn not_struct() -> Error {
+    Error::Message("to_columns can only serialize structs".into())
+}
+
+/// Sends the fields of a row to its `Columns`.
+struct RowSerializer<'c, 'a>(&'c mut Columns<'a>);
+
+macro_rules! not_struct {
+    ($($func:ident($($arg:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $func(self, $(_: $arg),*) -> Result<$ok> {
+                Err(not_struct())
+            }
+        )*
+    };
+}
+
+impl<'c, 'a> ser::Serializer for RowSerializer<'c, 'a> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    not_struct! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(&'static str) -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(not_struct())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(not_struct())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+}
+
+impl<'c, 'a> ser::SerializeStruct for RowSerializer<'c, 'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.0.push_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// `Compound` if a value isn't a scalar.
+#[derive(Debug)]
+enum CellError {
+    Compound,
+    Error(Error),
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellError::Compound => f.write_str("not a scalar"),
+            CellError::Error(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CellError {}
+
+impl ser::Error for CellError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CellError::Error(Error::Message(msg.to_string()))
+    }
+}
+
+/// Captures a scalar value as a `Cell`.
+struct CellSerializer<'a> {
+    enc: &'a Encoder<'a>,
+}
+
+macro_rules! cell {
+    ($func:ident, $type:ty, $cell:ident as $as:ty, canonical $canonical:ident as $cas:ty) => {
+        fn $func(self, val: $type) -> std::result::Result<Cell, CellError> {
+            if self.enc.canonical_numbers {
+                Ok(Cell::$canonical(val as $cas))
+            } else {
+                Ok(Cell::$cell(val as $as))
+            }
+        }
+    };
+}
+
+macro_rules! compound {
+    ($($func:ident($($arg:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $func(self, $(_: $arg),*) -> std::result::Result<$ok, CellError> {
+                Err(CellError::Compound)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for CellSerializer<'a> {
+    type Ok = Cell;
+    type Error = CellError;
+
+    type SerializeSeq = Impossible<Cell, CellError>;
+    type SerializeTuple = Impossible<Cell, CellError>;
+    type SerializeTupleStruct = Impossible<Cell, CellError>;
+    type SerializeTupleVariant = Impossible<Cell, CellError>;
+    type SerializeMap = Impossible<Cell, CellError>;
+    type SerializeStruct = Impossible<Cell, CellError>;
+    type SerializeStructVariant = Impossible<Cell, CellError>;
+
+    fn serialize_bool(self, val: bool) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Bool(val))
+    }
+
+    // the same types as ser.rs
+    cell!(serialize_i8, i8, Byte as i8, canonical Long as i64);
+    cell!(serialize_i16, i16, Short as i16, canonical Long as i64);
+    cell!(serialize_i32, i32, Int as i32, canonical Long as i64);
+    cell!(serialize_i64, i64, Long as i64, canonical Long as i64);
+    cell!(serialize_u8, u8, Short as i16, canonical Long as i64);
+    cell!(serialize_u16, u16, Int as i32, canonical Long as i64);
+    cell!(serialize_u32, u32, Long as i64, canonical Long as i64);
+    cell!(serialize_u64, u64, Long as i64, canonical Long as i64);
+    cell!(serialize_f32, f32, Float as f32, canonical Double as f64);
+    cell!(serialize_f64, f64, Double as f64, canonical Double as f64);
+
+    fn serialize_char(self, val: char) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Str(val.to_string()))
+    }
+
+    fn serialize_str(self, val: &str) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Str(val.to_owned()))
+    }
+
+    fn serialize_none(self) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        value: &T,
+    ) -> std::result::Result<Cell, CellError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> std::result::Result<Cell, CellError> {
+        Ok(Cell::Null)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> std::result::Result<Cell, CellError> {
+        // our markers and tagged literals need the real `Serializer`
+        if name.starts_with("$serde_clj::") || self.enc.tags.contains_key(name) {
+            Err(CellError::Compound)
+        } else {
+            value.serialize(self)
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> std::result::Result<Cell, CellError> {
+        Err(CellError::Compound)
+    }
+
+    compound! {
+        serialize_bytes(&[u8]) -> Cell;
+        serialize_unit_variant(&'static str, u32, &'static str) -> Cell;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+}
+
+/// Deserializes the columns produced by [`to_columns`](fn.to_columns.html)
+/// back into rows.
+///
+/// Columns may be primitive arrays, `Object[]`s or any sequence, and
+/// must all have the same length; masks in the `:missing` metadata
+/// mark `nil`s in primitive columns. Each row's fields are
+/// deserialized from its column values like `from_object` would,
+/// except that primitive values are accepted by any numeric type they
+/// fit in.
+pub fn from_columns<'a, T>(dec: &'a Decoder<'a>, obj: JObject<'a>) -> Result<Vec<T>>
+where
+    T: de::Deserialize<'a>,
+{
+    let env = &dec.com.env;
+    let obj = env.auto_local(obj);
+    let mut masks = HashMap::new();
+    let meta = dec.decode_meta(obj.as_obj())?;
+    if !meta.is_null() {
+        let mut meta: HashMap<ColumnName, Raw> = from_object(dec, meta)?;
+        if let Some(missing) = meta.remove("missing") {
+            let missing =
+                env.new_local_ref::<JObject>(JObject::from(missing.as_obj().into_inner()))?;
+            for (name, mask) in from_object::<HashMap<ColumnName, Raw>>(dec, missing)? {
+                let arr = mask.as_obj().into_inner();
+                let mut buf = vec![0; env.get_array_length(arr)? as usize];
+                env.get_boolean_array_region(arr, 0, &mut buf)?;
+                masks.insert(name.0, buf);
+            }
+        }
+    }
+
+    let cols: HashMap<ColumnName, Raw> =
+        from_object(dec, env.new_local_ref::<JObject>(obj.as_obj())?)?;
+    let mut names = Vec::with_capacity(cols.len());
+    let mut sources = Vec::with_capacity(cols.len());
+    for (name, col) in cols {
+        let local =
+            env.auto_local(env.new_local_ref::<JObject>(JObject::from(col.as_obj().into_inner()))?);
+        sources.push(Source::new(dec, local, masks.remove(&name.0))?);
+        names.push(name.0);
+    }
+    if sources.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut rows = Vec::new();
+    loop {
+        let i = rows.len();
+        let mut cells = Vec::with_capacity(sources.len());
+        for source in &mut sources {
+            if let Some(cell) = source.get(dec, i)? {
+                cells.push(cell);
+            }
+        }
+        if cells.is_empty() {
+            return Ok(rows);
+        } else if cells.len() < sources.len() {
+            return Err(Error::Message("columns have different lengths".into()));
+        }
+        rows.push(T::deserialize(Row {
+            dec,
+            names: &names,
+            cells,
+        })?);
+    }
+}
+
+/// A column name, which may be a keyword even with `Coercion::Strict`.
+#[derive(PartialEq, Eq, Hash)]
+struct ColumnName(String);
+
+impl std::borrow::Borrow<str> for ColumnName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'de> de::Deserialize<'de> for ColumnName {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = ColumnName;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a column name")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<ColumnName, E> {
+                Ok(ColumnName(v.to_owned()))
+            }
+        }
+
+        deserializer.deserialize_identifier(NameVisitor)
+    }
+}
+
+/// Where a column's values come from.
+enum Source<'a> {
+    Bool(Vec<u8>, Vec<u8>),
+    Long(Vec<i64>, Vec<u8>),
+    Double(Vec<f64>, Vec<u8>),
+    Array(AutoLocal<'a, 'a>, usize),
+    Seq(AutoLocal<'a, 'a>),
+}
+
+impl<'a> Source<'a> {
+    fn new(dec: &'a Decoder<'a>, col: AutoLocal<'a, 'a>, mask: Option<Vec<u8>>) -> Result<Self> {
+        let env = &dec.com.env;
+        let mask = mask.unwrap_or_default();
+        let obj = col.as_obj();
+        if dec.com.is_instance_of_name(obj, "[Z")? {
+            let mut buf = vec![0; env.get_array_length(obj.into_inner())? as usize];
+            env.get_boolean_array_region(obj.into_inner(), 0, &mut buf)?;
+            return Ok(Source::Bool(buf, mask));
+        }
+        let longs = [Kind::Long, Kind::Int, Kind::Short, Kind::Byte];
+        for &kind in &longs {
+            if let Some(bytes) = buffer::array_from_java(dec, obj, kind)? {
+                let values = match kind {
+                    Kind::Long => buffer::from_bytes::<i64>(&bytes),
+                    Kind::Int => widen(buffer::from_bytes::<i32>(&bytes)),
+                    Kind::Short => widen(buffer::from_bytes::<i16>(&bytes)),
+                    _ => widen(buffer::from_bytes::<i8>(&bytes)),
+                };
+                return Ok(Source::Long(values, mask));
+            }
+        }
+        if let Some(bytes) = buffer::array_from_java(dec, obj, Kind::Double)? {
+            return Ok(Source::Double(buffer::from_bytes(&bytes), mask));
+        }
+        if let Some(bytes) = buffer::array_from_java(dec, obj, Kind::Float)? {
+            return Ok(Source::Double(
+                widen(buffer::from_bytes::<f32>(&bytes)),
+                mask,
+            ));
+        }
+        if dec.com.is_instance_of_name(obj, "[Ljava/lang/Object;")? {
+            let len = env.get_array_length(obj.into_inner())? as usize;
+            return Ok(Source::Array(col, len));
+        }
+        Ok(Source::Seq(dec.start_seq(col)?))
+    }
+
+    /// The value in row `i`, which must be one past the previous row.
+    fn get(&mut self, dec: &'a Decoder<'a>, i: usize) -> Result<Option<Value<'a>>> {
+        let missing = |mask: &[u8]| mask.get(i).is_some_and(|&m| m != 0);
+        Ok(match self {
+            Source::Bool(v, mask) => v.get(i).map(|&x| match missing(mask) {
+                true => Value::Missing,
+                false => Value::Bool(x != 0),
+            }),
+            Source::Long(v, mask) => v.get(i).map(|&x| match missing(mask) {
+                true => Value::Missing,
+                false => Value::Long(x),
+            }),
+            Source::Double(v, mask) => v.get(i).map(|&x| match missing(mask) {
+                true => Value::Missing,
+                false => Value::Double(x),
+            }),
+            Source::Array(arr, len) if i < *len => {
+                let env = &dec.com.env;
+                let elem = env.get_object_array_element(arr.as_obj().into_inner(), i as i32)?;
+                Some(Value::Object(env.auto_local(elem)))
+            }
+            Source::Array(..) => None,
+            Source::Seq(seq) => dec.next_element(seq)?.map(Value::Object),
+        })
+    }
+}
+
+fn widen<T: Copy, U: From<T>>(values: Vec<T>) -> Vec<U> {
+    values.into_iter().map(U::from).collect()
+}
+
+enum Value<'a> {
+    Missing,
+    Bool(bool),
+    Long(i64),
+    Double(f64),
+    Object(AutoLocal<'a, 'a>),
+}
+
+/// One row, as a map from column name to value.
+struct Row<'r, 'a> {
+    dec: &'a Decoder<'a>,
+    names: &'r [String],
+    cells: Vec<Value<'a>>,
+}
+
+impl<'r, 'a> de::Deserializer<'a> for Row<'r, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(RowAccess {
+            dec: self.dec,
+            names: self.names.iter(),
+            cells: self.cells.into_iter(),
+            next: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        <W: Visitor<'a>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'r, 'a> {
+    dec: &'a Decoder<'a>,
+    names: std::slice::Iter<'r, String>,
+    cells: std::vec::IntoIter<Value<'a>>,
+    next: Option<Value<'a>>,
+}
+
+impl<'r, 'a> MapAccess<'a> for RowAccess<'r, 'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'a>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match (self.names.next(), self.cells.next()) {
+            (Some(name), Some(cell)) => {
+                self.next = Some(cell);
+                seed.deserialize(name.as_str().into_deserializer())
+                    .map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'a>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.next.take() {
+            Some(Value::Object(obj)) => seed.deserialize(Deserializer::from_object(self.dec, obj)),
+            Some(value) => seed.deserialize(value),
+            None => Err(Error::Message("value requested before key".into())),
+        }
+    }
+}
+
+/// A value from a primitive column.
+impl<'de, 'a> de::Deserializer<'de> for Value<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Missing => visitor.visit_none(),
+            Value::Bool(x) => visitor.visit_bool(x),
+            Value::Long(x) => visitor.visit_i64(x),
+            Value::Double(x) => visitor.visit_f64(x),
+            Value::Object(_) => Err(Error::Message("unexpected object".into())),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Missing => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
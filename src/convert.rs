@@ -1,11 +1,32 @@
-use crate::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::bean::{self, Accessors, Builder};
+use crate::fns::catch_exception;
+use crate::{Error, Result};
 
 use jni::{
-    objects::{AutoLocal, JClass, JMethodID, JObject, JStaticMethodID, JValue},
+    descriptors::Desc,
+    objects::{
+        AutoLocal, GlobalRef, JClass, JFieldID, JMethodID, JObject, JStaticMethodID, JValue,
+    },
     signature::{JavaType, Primitive},
+    sys::jmethodID,
     JNIEnv,
 };
 
+/// Replaces the local class refs in the fields `$field` of `$s` with
+/// global ones, which are pushed onto `$globals` (see `Pinned`).
+macro_rules! pin {
+    ($env:expr, $globals:expr, $s:expr, $($field:ident),+ $(,)?) => {
+        $(
+            let global = $env.new_global_ref($s.$field)?;
+            $s.$field = JClass::from(global.as_obj().into_inner());
+            $globals.push(global);
+        )+
+    };
+}
+
 pub(crate) struct Common<'a> {
     pub(crate) env: JNIEnv<'a>,
     pub(crate) class_boolean: JClass<'a>,
@@ -17,13 +38,49 @@ pub(crate) struct Common<'a> {
     pub(crate) class_double: JClass<'a>,
     pub(crate) class_character: JClass<'a>,
     pub(crate) class_string: JClass<'a>,
-    pub(crate) class_persistentvector: JClass<'a>,
-    pub(crate) class_persistenthashmap: JClass<'a>,
-    pub(crate) class_imapiterable: JClass<'a>,
-    pub(crate) class_keyword: JClass<'a>,
+    /// classes looked up by name, see `class`
+    classes: RefCell<HashMap<&'static str, GlobalRef>>,
 }
 
 impl<'a> Common<'a> {
+    /// Like `JNIEnv::is_instance_of`, except that `null` isn't an
+    /// instance of anything.
+    pub(crate) fn is_instance_of<'c, O, C>(&self, obj: O, class: C) -> Result<bool>
+    where
+        O: Into<JObject<'a>>,
+        C: Desc<'a, JClass<'c>>,
+    {
+        let obj = obj.into();
+        Ok(!obj.is_null() && self.env.is_instance_of(obj, class)?)
+    }
+
+    /// `is_instance_of` for a class given by name, e.g.
+    /// `"java/util/UUID"`.
+    pub(crate) fn is_instance_of_name<O>(&self, obj: O, name: &'static str) -> Result<bool>
+    where
+        O: Into<JObject<'a>>,
+    {
+        let obj = obj.into();
+        if obj.is_null() {
+            return Ok(false);
+        }
+        let class = self.class(name)?;
+        Ok(self.env.is_instance_of(obj, JClass::from(class.as_obj()))?)
+    }
+
+    /// The class `name`, looked up on first use. Looking classes up by
+    /// name through `Desc` leaks a local ref each time, which adds up
+    /// when decoding many values.
+    pub(crate) fn class(&self, name: &'static str) -> Result<GlobalRef> {
+        if let Some(class) = self.classes.borrow().get(name) {
+            return Ok(class.clone());
+        }
+        let class = self.env.auto_local(self.env.find_class(name)?);
+        let class = self.env.new_global_ref(class.as_obj())?;
+        self.classes.borrow_mut().insert(name, class.clone());
+        Ok(class)
+    }
+
     pub fn new(env: JNIEnv<'a>) -> Result<Self> {
         Ok(Self {
             class_boolean: env.find_class("java/lang/Boolean")?,
@@ -35,13 +92,29 @@ impl<'a> Common<'a> {
             class_double: env.find_class("java/lang/Double")?,
             class_character: env.find_class("java/lang/Character")?,
             class_string: env.find_class("java/lang/String")?,
-            class_keyword: env.find_class("clojure/lang/Keyword")?,
-            class_persistentvector: env.find_class("clojure/lang/PersistentVector")?,
-            class_persistenthashmap: env.find_class("clojure/lang/PersistentHashMap")?,
-            class_imapiterable: env.find_class("clojure/lang/IMapIterable")?,
+            classes: RefCell::new(HashMap::new()),
             env,
         })
     }
+
+    fn pin(&mut self, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        let env = self.env.clone();
+        pin!(
+            env,
+            globals,
+            self,
+            class_boolean,
+            class_byte,
+            class_integer,
+            class_short,
+            class_long,
+            class_float,
+            class_double,
+            class_character,
+            class_string,
+        );
+        Ok(())
+    }
 }
 
 pub struct Encoder<'a> {
@@ -59,15 +132,338 @@ pub struct Encoder<'a> {
     new_arraylist: JMethodID<'a>,
     add_arraylist: JMethodID<'a>,
     toarray_arraylist: JMethodID<'a>,
+    get_arraylist: JMethodID<'a>,
+
+    target: EncoderTarget<'a>,
+
+    pub(crate) canonical_numbers: bool,
+    pub(crate) omit_nil_fields: bool,
+    pub(crate) type_meta: bool,
+    #[cfg(feature = "ndarray")]
+    pub(crate) shaped_arrays: bool,
+    /// newtype struct name -> tag
+    pub(crate) tags: HashMap<String, String>,
+    /// struct name -> record class name
+    pub(crate) records: HashMap<String, String>,
+    /// record class name -> class and `create` method (raw, so that
+    /// `Encoder` stays covariant)
+    record_classes: RefCell<HashMap<String, (GlobalRef, jmethodID)>>,
+    /// struct name -> Java bean or record class name
+    pub(crate) beans: HashMap<String, String>,
+    /// class name -> how to construct it
+    bean_builders: RefCell<HashMap<String, Builder>>,
+    /// enum name -> Java enum class name
+    pub(crate) java_enums: HashMap<String, String>,
+    enum_classes: RefCell<HashMap<String, GlobalRef>>,
+}
+
+/// See `Encoder::options`.
+#[derive(Clone)]
+pub(crate) struct EncoderOptions {
+    clojure: bool,
+    canonical_numbers: bool,
+    omit_nil_fields: bool,
+    type_meta: bool,
+    #[cfg(feature = "ndarray")]
+    shaped_arrays: bool,
+    tags: HashMap<String, String>,
+    records: HashMap<String, String>,
+    beans: HashMap<String, String>,
+    java_enums: HashMap<String, String>,
+}
+
+impl EncoderOptions {
+    pub(crate) fn encoder<'a>(&self, env: JNIEnv<'a>) -> Result<Encoder<'a>> {
+        let mut enc = if self.clojure {
+            Encoder::new(env)?
+        } else {
+            Encoder::java(env)?
+        };
+        enc.canonical_numbers = self.canonical_numbers;
+        enc.omit_nil_fields = self.omit_nil_fields;
+        enc.type_meta = self.type_meta;
+        #[cfg(feature = "ndarray")]
+        {
+            enc.shaped_arrays = self.shaped_arrays;
+        }
+        enc.tags = self.tags.clone();
+        enc.records = self.records.clone();
+        enc.beans = self.beans.clone();
+        enc.java_enums = self.java_enums.clone();
+        Ok(enc)
+    }
+}
+
+/// An `Encoder` (made from `EncoderOptions`) and a Clojure `Decoder`
+/// whose class refs are global, so that they can be kept between
+/// native calls (by `RustFn` and `lazy_seq`) rather than looking up
+/// dozens of classes and methods on every call.
+pub(crate) struct Pinned {
+    enc: Encoder<'static>,
+    dec: Decoder<'static>,
+    _globals: Vec<GlobalRef>,
+}
+
+// Everything the `Encoder` and `Decoder` refer to is global, and `bind`
+// replaces their env with the current thread's before each use.
+unsafe impl Send for Pinned {}
 
+impl Pinned {
+    pub(crate) fn new(options: &EncoderOptions, env: &JNIEnv) -> Result<Self> {
+        let env = unsafe { JNIEnv::from_raw(env.get_native_interface())? };
+        let mut globals = Vec::new();
+        let mut enc = options.encoder(env.clone())?;
+        enc.pin(&mut globals)?;
+        let mut dec = Decoder::new(env)?;
+        dec.pin(&mut globals)?;
+        Ok(Self {
+            enc,
+            dec,
+            _globals: globals,
+        })
+    }
+
+    /// The `Encoder` and `Decoder`, for use with `env`.
+    pub(crate) fn bind<'b>(&'b mut self, env: &JNIEnv<'b>) -> (&'b Encoder<'b>, &'b Decoder<'b>) {
+        let env = unsafe { JNIEnv::from_raw(env.get_native_interface()) }.unwrap();
+        self.enc.com.env = env.clone();
+        self.dec.com.env = env;
+        // nothing borrows from the last call's buffers anymore
+        self.dec.lent.get_mut().clear();
+        (&self.enc, &self.dec)
+    }
+}
+
+enum EncoderTarget<'a> {
+    Clojure(ClojureEncoder<'a>),
+    Java(JavaEncoder<'a>),
+}
+
+struct ClojureEncoder<'a> {
+    class_keyword: JClass<'a>,
     intern_keyword: JStaticMethodID<'a>,
+    class_symbol: JClass<'a>,
+    intern_symbol: JStaticMethodID<'a>,
 
+    class_persistentvector: JClass<'a>,
     create_persistentvector: JStaticMethodID<'a>,
+    class_persistenthashmap: JClass<'a>,
     create_persistenthashmap: JStaticMethodID<'a>,
+
+    class_persistentlist: JClass<'a>,
+    create_persistentlist: JStaticMethodID<'a>,
+    class_persistenthashset: JClass<'a>,
+    create_persistenthashset: JStaticMethodID<'a>,
+    class_persistenttreeset: JClass<'a>,
+    create_persistenttreeset: JStaticMethodID<'a>,
+    class_persistenttreemap: JClass<'a>,
+    create_persistenttreemap: JStaticMethodID<'a>,
+    class_rt: JClass<'a>,
+    seq_rt: JStaticMethodID<'a>,
+    classforname_rt: JStaticMethodID<'a>,
+
+    class_iobj: JClass<'a>,
+    withmeta_iobj: JMethodID<'a>,
+    class_ipersistentmap: JClass<'a>,
+
+    class_taggedliteral: JClass<'a>,
+    create_taggedliteral: JStaticMethodID<'a>,
+}
+
+impl<'a> ClojureEncoder<'a> {
+    fn new(env: &JNIEnv<'a>) -> Result<Self> {
+        let class_keyword = env.find_class("clojure/lang/Keyword")?;
+        let class_symbol = env.find_class("clojure/lang/Symbol")?;
+        let class_persistentvector = env.find_class("clojure/lang/PersistentVector")?;
+        let class_persistenthashmap = env.find_class("clojure/lang/PersistentHashMap")?;
+        let class_persistentlist = env.find_class("clojure/lang/PersistentList")?;
+        let class_persistenthashset = env.find_class("clojure/lang/PersistentHashSet")?;
+        let class_persistenttreeset = env.find_class("clojure/lang/PersistentTreeSet")?;
+        let class_persistenttreemap = env.find_class("clojure/lang/PersistentTreeMap")?;
+        let class_rt = env.find_class("clojure/lang/RT")?;
+        let class_iobj = env.find_class("clojure/lang/IObj")?;
+        let class_taggedliteral = env.find_class("clojure/lang/TaggedLiteral")?;
+        Ok(Self {
+            intern_keyword: env.get_static_method_id(
+                class_keyword,
+                "intern",
+                "(Ljava/lang/String;)Lclojure/lang/Keyword;",
+            )?,
+            class_keyword,
+            intern_symbol: env.get_static_method_id(
+                class_symbol,
+                "intern",
+                "(Ljava/lang/String;)Lclojure/lang/Symbol;",
+            )?,
+            class_symbol,
+
+            create_persistentvector: env.get_static_method_id(
+                class_persistentvector,
+                "create",
+                "(Ljava/lang/Iterable;)Lclojure/lang/PersistentVector;",
+            )?,
+            class_persistentvector,
+
+            create_persistenthashmap: env.get_static_method_id(
+                class_persistenthashmap,
+                "create",
+                "([Ljava/lang/Object;)Lclojure/lang/PersistentHashMap;",
+            )?,
+            class_persistenthashmap,
+
+            create_persistentlist: env.get_static_method_id(
+                class_persistentlist,
+                "create",
+                "(Ljava/util/List;)Lclojure/lang/IPersistentList;",
+            )?,
+            class_persistentlist,
+            create_persistenthashset: env.get_static_method_id(
+                class_persistenthashset,
+                "create",
+                "(Ljava/util/List;)Lclojure/lang/PersistentHashSet;",
+            )?,
+            class_persistenthashset,
+            create_persistenttreeset: env.get_static_method_id(
+                class_persistenttreeset,
+                "create",
+                "(Lclojure/lang/ISeq;)Lclojure/lang/PersistentTreeSet;",
+            )?,
+            class_persistenttreeset,
+            create_persistenttreemap: env.get_static_method_id(
+                class_persistenttreemap,
+                "create",
+                "(Lclojure/lang/ISeq;)Lclojure/lang/PersistentTreeMap;",
+            )?,
+            class_persistenttreemap,
+            seq_rt: env.get_static_method_id(
+                class_rt,
+                "seq",
+                "(Ljava/lang/Object;)Lclojure/lang/ISeq;",
+            )?,
+            classforname_rt: env.get_static_method_id(
+                class_rt,
+                "classForName",
+                "(Ljava/lang/String;)Ljava/lang/Class;",
+            )?,
+            class_rt,
+            withmeta_iobj: env.get_method_id(
+                class_iobj,
+                "withMeta",
+                "(Lclojure/lang/IPersistentMap;)Lclojure/lang/IObj;",
+            )?,
+            class_iobj,
+            class_ipersistentmap: env.find_class("clojure/lang/IPersistentMap")?,
+            create_taggedliteral: env.get_static_method_id(
+                class_taggedliteral,
+                "create",
+                "(Lclojure/lang/Symbol;Ljava/lang/Object;)Lclojure/lang/TaggedLiteral;",
+            )?,
+            class_taggedliteral,
+        })
+    }
+
+    fn pin(&mut self, env: &JNIEnv, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        pin!(
+            env,
+            globals,
+            self,
+            class_keyword,
+            class_symbol,
+            class_persistentvector,
+            class_persistenthashmap,
+            class_persistentlist,
+            class_persistenthashset,
+            class_persistenttreeset,
+            class_persistenttreemap,
+            class_rt,
+            class_iobj,
+            class_ipersistentmap,
+            class_taggedliteral,
+        );
+        Ok(())
+    }
+}
+
+/// Plain `java.util` collections, for callers without Clojure.
+struct JavaEncoder<'a> {
+    class_linkedhashmap: JClass<'a>,
+    new_linkedhashmap: JMethodID<'a>,
+    put_map: JMethodID<'a>,
+    class_hashset: JClass<'a>,
+    new_hashset: JMethodID<'a>,
+    class_treeset: JClass<'a>,
+    new_treeset: JMethodID<'a>,
+    class_treemap: JClass<'a>,
+    new_treemap: JMethodID<'a>,
+}
+
+impl<'a> JavaEncoder<'a> {
+    fn new(env: &JNIEnv<'a>) -> Result<Self> {
+        let class_linkedhashmap = env.find_class("java/util/LinkedHashMap")?;
+        let class_hashset = env.find_class("java/util/HashSet")?;
+        let class_treeset = env.find_class("java/util/TreeSet")?;
+        let class_treemap = env.find_class("java/util/TreeMap")?;
+        Ok(Self {
+            new_linkedhashmap: env.get_method_id(class_linkedhashmap, "<init>", "()V")?,
+            put_map: env.get_method_id(
+                "java/util/Map",
+                "put",
+                "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+            )?,
+            class_linkedhashmap,
+            new_hashset: env.get_method_id(class_hashset, "<init>", "(Ljava/util/Collection;)V")?,
+            class_hashset,
+            new_treeset: env.get_method_id(class_treeset, "<init>", "(Ljava/util/Collection;)V")?,
+            class_treeset,
+            new_treemap: env.get_method_id(class_treemap, "<init>", "(Ljava/util/Map;)V")?,
+            class_treemap,
+        })
+    }
+
+    fn pin(&mut self, env: &JNIEnv, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        pin!(
+            env,
+            globals,
+            self,
+            class_linkedhashmap,
+            class_hashset,
+            class_treeset,
+            class_treemap,
+        );
+        Ok(())
+    }
 }
 
 impl<'a> Encoder<'a> {
+    /// An `Encoder` producing Clojure data.
     pub fn new(env: JNIEnv<'a>) -> Result<Self> {
+        let target = EncoderTarget::Clojure(ClojureEncoder::new(&env)?);
+        Self::with_target(env, target)
+    }
+
+    /// An `Encoder` producing plain Java collections, which doesn't
+    /// need Clojure on the classpath. Sequences become `ArrayList`s,
+    /// maps `LinkedHashMap`s (`TreeMap`s for `as_sorted_map`) and sets
+    /// `HashSet`s (`TreeSet`s). Struct fields, unit variants, keywords
+    /// and symbols become strings. The Clojure-specific options
+    /// (`type_meta`, `tag_newtype`, `record`) and `WithMeta` metadata
+    /// are ignored.
+    pub fn java(env: JNIEnv<'a>) -> Result<Self> {
+        let target = EncoderTarget::Java(JavaEncoder::new(&env)?);
+        Self::with_target(env, target)
+    }
+
+    fn pin(&mut self, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        self.com.pin(globals)?;
+        let env = self.com.env.clone();
+        pin!(env, globals, self, class_arraylist);
+        match &mut self.target {
+            EncoderTarget::Clojure(clj) => clj.pin(&env, globals),
+            EncoderTarget::Java(java) => java.pin(&env, globals),
+        }
+    }
+
+    fn with_target(env: JNIEnv<'a>, target: EncoderTarget<'a>) -> Result<Self> {
         let com = Common::new(env)?;
 
         let class_arraylist = com.env.find_class("java/util/ArrayList")?;
@@ -124,37 +520,151 @@ impl<'a> Encoder<'a> {
                 "toArray",
                 "()[Ljava/lang/Object;",
             )?,
-            class_arraylist,
-            intern_keyword: com.env.get_static_method_id(
-                com.class_keyword,
-                "intern",
-                "(Ljava/lang/String;)Lclojure/lang/Keyword;",
+            get_arraylist: com.env.get_method_id(
+                class_arraylist,
+                "get",
+                "(I)Ljava/lang/Object;",
             )?,
+            class_arraylist,
 
-            create_persistentvector: com.env.get_static_method_id(
-                com.class_persistentvector,
-                "create",
-                "(Ljava/lang/Iterable;)Lclojure/lang/PersistentVector;",
-            )?,
+            target,
 
-            create_persistenthashmap: com.env.get_static_method_id(
-                com.class_persistenthashmap,
-                "create",
-                "([Ljava/lang/Object;)Lclojure/lang/PersistentHashMap;",
-            )?,
+            canonical_numbers: false,
+            omit_nil_fields: false,
+            type_meta: false,
+            #[cfg(feature = "ndarray")]
+            shaped_arrays: false,
+            tags: HashMap::new(),
+            records: HashMap::new(),
+            record_classes: RefCell::new(HashMap::new()),
+            beans: HashMap::new(),
+            bean_builders: RefCell::new(HashMap::new()),
+            java_enums: HashMap::new(),
+            enum_classes: RefCell::new(HashMap::new()),
 
             com,
         })
     }
 
+    /// Box every integer as a `java.lang.Long` and every float as a
+    /// `java.lang.Double`, like the Clojure reader does, instead of
+    /// picking the closest Java type (`Byte`, `Short`, `Integer`,
+    /// `Float`).
+    pub fn canonical_numbers(mut self, canonical: bool) -> Self {
+        self.canonical_numbers = canonical;
+        self
+    }
+
+    /// Leave struct fields which serialize to `nil` (e.g. `None`) out
+    /// of the map, instead of emitting `:field nil`.
+    pub fn omit_nil_fields(mut self, omit: bool) -> Self {
+        self.omit_nil_fields = omit;
+        self
+    }
+
+    /// Serialize `ndarray` arrays (see
+    /// [`as_ndarray`](as_ndarray/index.html)) as `{:shape [2 3] :data
+    /// <double[]>}`, with the elements in row-major order in a
+    /// primitive array, instead of as nested vectors.
+    #[cfg(feature = "ndarray")]
+    pub fn shaped_arrays(mut self, shaped: bool) -> Self {
+        self.shaped_arrays = shaped;
+        self
+    }
+
+    /// Attach `{:rust/type "Name"}` metadata to serialized structs,
+    /// where `Name` is the struct's name as serde sees it. That is the
+    /// bare name, since serde doesn't know the module path: for a
+    /// qualified one, rename the struct, e.g. `#[serde(rename =
+    /// "my_crate::model::Person")]` (which is also the name `record`
+    /// and `bean` then match).
+    pub fn type_meta(mut self, type_meta: bool) -> Self {
+        self.type_meta = type_meta;
+        self
+    }
+
+    /// Serialize the newtype struct `name` (e.g. `UserId`) as a
+    /// `clojure.lang.TaggedLiteral` with the symbol `tag` (e.g.
+    /// `my/UserId`), which prints as `#my/UserId 42`, instead of as
+    /// just its contents.
+    pub fn tag_newtype(mut self, name: &str, tag: &str) -> Self {
+        self.tags.insert(name.into(), tag.into());
+        self
+    }
+
+    /// Serialize the struct `name` as an instance of the record
+    /// `class` (e.g. `my.ns.Point`, from `(defrecord Point ...)` in
+    /// `my.ns`), using its `create` factory. The struct's fields
+    /// become the record's fields, or extra keys if the record has no
+    /// such field.
+    pub fn record(mut self, name: &str, class: &str) -> Self {
+        self.records.insert(name.into(), class.into());
+        self
+    }
+
+    /// Serialize the struct `name` as an instance of the Java class
+    /// `class` (e.g. `com.example.Person`), found by reflection. A
+    /// Java `record` is built with its canonical constructor, any
+    /// other class with its public no-argument constructor and a
+    /// setter per field. Fields match the Java property with the same
+    /// name or its camel case version (`first_name` -> `firstName`).
+    /// Numbers are converted to the type of the constructor or setter
+    /// parameter (an error if they don't fit), and of overloaded
+    /// setters the one taking the value's type is preferred.
+    pub fn bean(mut self, name: &str, class: &str) -> Self {
+        self.beans.insert(name.into(), class.into());
+        self
+    }
+
+    /// Serialize the unit variants of the enum `name` as constants of
+    /// the Java enum `class` (e.g. `java.time.DayOfWeek`) instead of
+    /// as keywords. Variants are looked up by name, so you'll usually
+    /// want `#[serde(rename_all = "SCREAMING_SNAKE_CASE")]`. Java enum
+    /// constants deserialize into unit variants without this.
+    pub fn java_enum(mut self, name: &str, class: &str) -> Self {
+        self.java_enums.insert(name.into(), class.into());
+        self
+    }
+
+    /// Whether this encodes Clojure data rather than plain Java.
+    pub(crate) fn is_clojure(&self) -> bool {
+        matches!(self.target, EncoderTarget::Clojure(_))
+    }
+
+    /// The target and options, without any JNI references, for making
+    /// an equivalent `Encoder` in a later native call.
+    pub(crate) fn options(&self) -> EncoderOptions {
+        EncoderOptions {
+            clojure: self.is_clojure(),
+            canonical_numbers: self.canonical_numbers,
+            omit_nil_fields: self.omit_nil_fields,
+            type_meta: self.type_meta,
+            #[cfg(feature = "ndarray")]
+            shaped_arrays: self.shaped_arrays,
+            tags: self.tags.clone(),
+            records: self.records.clone(),
+            beans: self.beans.clone(),
+            java_enums: self.java_enums.clone(),
+        }
+    }
+
+    fn string(&'a self, s: &str) -> Result<JObject<'a>> {
+        Ok(self.com.env.new_string(s)?.into())
+    }
+
+    /// A keyword (or, for plain Java, a string).
     pub(crate) fn get_keyword(&'a self, name: &str) -> Result<JObject<'a>> {
+        let clj = match &self.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return self.string(name),
+        };
         let s = self.com.env.auto_local(self.com.env.new_string(name)?);
         let k = self
             .com
             .env
             .call_static_method_unchecked(
-                self.com.class_keyword,
-                self.intern_keyword,
+                clj.class_keyword,
+                clj.intern_keyword,
                 JavaType::Object(String::new()),
                 &[s.as_obj().into()],
             )?
@@ -162,6 +672,191 @@ impl<'a> Encoder<'a> {
         Ok(k)
     }
 
+    /// A symbol (or, for plain Java, a string).
+    pub(crate) fn get_symbol(&'a self, name: &str) -> Result<JObject<'a>> {
+        let clj = match &self.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return self.string(name),
+        };
+        let s = self.com.env.auto_local(self.com.env.new_string(name)?);
+        let sym = self
+            .com
+            .env
+            .call_static_method_unchecked(
+                clj.class_symbol,
+                clj.intern_symbol,
+                JavaType::Object(String::new()),
+                &[s.as_obj().into()],
+            )?
+            .l()?;
+        Ok(sym)
+    }
+
+    /// Wraps `form` in a tagged literal. This will invalidate the
+    /// local ref `form`!
+    pub(crate) fn tagged(&'a self, tag: &str, form: JObject<'a>) -> Result<JObject<'a>> {
+        let clj = match &self.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return Ok(form),
+        };
+        let env = &self.com.env;
+        let tag = env.auto_local(self.get_symbol(tag)?);
+        let form = env.auto_local(form);
+        Ok(env
+            .call_static_method_unchecked(
+                clj.class_taggedliteral,
+                clj.create_taggedliteral,
+                JavaType::Object(String::new()),
+                &[tag.as_obj().into(), form.as_obj().into()],
+            )?
+            .l()?)
+    }
+
+    /// Converts the map `obj` into the record `class`. This will
+    /// invalidate the local ref `obj`!
+    pub(crate) fn to_record(&'a self, obj: JObject<'a>, class: &str) -> Result<JObject<'a>> {
+        let clj = match &self.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return Ok(obj),
+        };
+        let env = &self.com.env;
+        let obj = env.auto_local(obj);
+        let mut classes = self.record_classes.borrow_mut();
+        let (record, create) = match classes.get(class) {
+            Some(found) => found,
+            None => {
+                // Records are usually defined by Clojure's own class
+                // loader, which FindClass doesn't know about.
+                let name = env.auto_local(env.new_string(class)?);
+                let record = env.auto_local(
+                    env.call_static_method_unchecked(
+                        clj.class_rt,
+                        clj.classforname_rt,
+                        JavaType::Object(String::new()),
+                        &[name.as_obj().into()],
+                    )?
+                    .l()?,
+                );
+                let sig = format!(
+                    "(Lclojure/lang/IPersistentMap;)L{};",
+                    class.replace('.', "/")
+                );
+                let create =
+                    env.get_static_method_id(JClass::from(record.as_obj()), "create", sig)?;
+                let record = env.new_global_ref(record.as_obj())?;
+                classes
+                    .entry(class.into())
+                    .or_insert((record, create.into_inner()))
+            }
+        };
+        Ok(env
+            .call_static_method_unchecked(
+                JClass::from(record.as_obj().into_inner()),
+                JStaticMethodID::from(*create),
+                JavaType::Object(String::new()),
+                &[obj.as_obj().into()],
+            )?
+            .l()?)
+    }
+
+    /// An instance of the bean or record `class` with the given
+    /// fields.
+    pub(crate) fn to_bean(
+        &'a self,
+        fields: &[(String, AutoLocal<'a, '_>)],
+        class: &str,
+    ) -> Result<JObject<'a>> {
+        let env = &self.com.env;
+        let mut builders = self.bean_builders.borrow_mut();
+        let builder = match builders.get(class) {
+            Some(builder) => builder,
+            None => {
+                let loaded = bean::load_class(env, class)?;
+                let builder = Builder::new(env, &loaded, class)?;
+                builders.entry(class.into()).or_insert(builder)
+            }
+        };
+        builder.build(self, fields)
+    }
+
+    /// The constant `variant` of the Java enum `class`.
+    pub(crate) fn enum_constant(&'a self, class: &str, variant: &str) -> Result<JObject<'a>> {
+        let env = &self.com.env;
+        let mut classes = self.enum_classes.borrow_mut();
+        let loaded = match classes.get(class) {
+            Some(loaded) => loaded,
+            None => {
+                let loaded = bean::load_class(env, class)?;
+                classes.entry(class.into()).or_insert(loaded)
+            }
+        };
+        let name = env.auto_local(env.new_string(variant)?);
+        let class_enum = self.com.class("java/lang/Enum")?;
+        let constant = env
+            .call_static_method(
+                JClass::from(class_enum.as_obj()),
+                "valueOf",
+                "(Ljava/lang/Class;Ljava/lang/String;)Ljava/lang/Enum;",
+                &[loaded.as_obj().into(), name.as_obj().into()],
+            )
+            .map_err(Error::from);
+        match catch_exception(env, constant) {
+            Ok(constant) => Ok(constant.l()?),
+            // "No enum constant ..."
+            Err(Error::Exception(ref desc))
+                if desc.starts_with("java.lang.IllegalArgumentException") =>
+            {
+                Err(Error::Message(format!(
+                    "{} has no constant {}",
+                    class, variant
+                )))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns `obj` with metadata `meta` (which must be a map or
+    /// `nil`). This will invalidate the local refs `obj` and `meta`!
+    pub(crate) fn with_meta(&'a self, obj: JObject<'a>, meta: JObject<'a>) -> Result<JObject<'a>> {
+        let env = &self.com.env;
+        let meta = env.auto_local(meta);
+        let clj = match &self.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return Ok(obj),
+        };
+        let obj = env.auto_local(obj);
+        if !self.com.is_instance_of(obj.as_obj(), clj.class_iobj)? {
+            return Err(Error::Message("can't attach metadata to this value".into()));
+        }
+        if !meta.as_obj().is_null()
+            && !self
+                .com
+                .is_instance_of(meta.as_obj(), clj.class_ipersistentmap)?
+        {
+            return Err(Error::Message("metadata must be a map".into()));
+        }
+        Ok(env
+            .call_method_unchecked(
+                obj.as_obj(),
+                clj.withmeta_iobj,
+                JavaType::Object(String::new()),
+                &[meta.as_obj().into()],
+            )?
+            .l()?)
+    }
+
+    /// Attaches `{:rust/type name}` to the struct `obj` (see
+    /// `type_meta`).
+    pub(crate) fn with_type_meta(&'a self, obj: JObject<'a>, name: &str) -> Result<JObject<'a>> {
+        if !self.is_clojure() {
+            return Ok(obj);
+        }
+        let meta = ArrayList::new(self)?;
+        meta.add(self.get_keyword("rust/type")?)?;
+        meta.add(self.com.env.new_string(name)?.into())?;
+        self.with_meta(obj, meta.into_hashmap()?)
+    }
+
     #[inline]
     pub(crate) fn to_boxed(&self, val: JValue<'a>) -> Result<JObject<'a>> {
         let com = &self.com;
@@ -250,22 +945,145 @@ impl<'a> ArrayList<'a> {
         Ok(())
     }
 
-    pub fn to_vector(self) -> Result<JObject<'a>> {
+    pub fn into_vector(self) -> Result<JObject<'a>> {
+        let clj = match &self.enc.target {
+            EncoderTarget::Clojure(clj) => clj,
+            EncoderTarget::Java(_) => return Ok(self.obj.forget()),
+        };
         Ok(self
             .enc
             .com
             .env
             .call_static_method_unchecked(
-                self.enc.com.class_persistentvector,
-                self.enc.create_persistentvector,
+                clj.class_persistentvector,
+                clj.create_persistentvector,
                 JavaType::Object(String::new()),
                 &[self.obj.as_obj().into()],
             )?
             .l()?)
     }
 
-    pub fn to_hashmap(self) -> Result<JObject<'a>> {
-        let arr = self.enc.com.env.auto_local(
+    /// Calls the static factory `method` on `class` with the list, or
+    /// (if `seq` is true) a seq of it.
+    fn create(
+        self,
+        clj: &ClojureEncoder<'a>,
+        class: JClass<'a>,
+        method: JStaticMethodID<'a>,
+        seq: bool,
+    ) -> Result<JObject<'a>> {
+        let env = &self.enc.com.env;
+        let items = if seq {
+            Some(
+                env.auto_local(
+                    env.call_static_method_unchecked(
+                        clj.class_rt,
+                        clj.seq_rt,
+                        JavaType::Object(String::new()),
+                        &[self.obj.as_obj().into()],
+                    )?
+                    .l()?,
+                ),
+            )
+        } else {
+            None
+        };
+        Ok(env
+            .call_static_method_unchecked(
+                class,
+                method,
+                JavaType::Object(String::new()),
+                &[items.as_ref().unwrap_or(&self.obj).as_obj().into()],
+            )?
+            .l()?)
+    }
+
+    /// Calls the constructor `method` of `class` with the list.
+    fn construct(self, class: JClass<'a>, method: JMethodID<'a>) -> Result<JObject<'a>> {
+        Ok(self
+            .enc
+            .com
+            .env
+            .new_object_unchecked(class, method, &[self.obj.as_obj().into()])?)
+    }
+
+    pub fn into_list(self) -> Result<JObject<'a>> {
+        match &self.enc.target {
+            EncoderTarget::Clojure(clj) => self.create(
+                clj,
+                clj.class_persistentlist,
+                clj.create_persistentlist,
+                false,
+            ),
+            EncoderTarget::Java(_) => Ok(self.obj.forget()),
+        }
+    }
+
+    pub fn into_set(self) -> Result<JObject<'a>> {
+        match &self.enc.target {
+            EncoderTarget::Clojure(clj) => self.create(
+                clj,
+                clj.class_persistenthashset,
+                clj.create_persistenthashset,
+                false,
+            ),
+            EncoderTarget::Java(java) => self.construct(java.class_hashset, java.new_hashset),
+        }
+    }
+
+    pub fn into_sorted_set(self) -> Result<JObject<'a>> {
+        match &self.enc.target {
+            EncoderTarget::Clojure(clj) => self.create(
+                clj,
+                clj.class_persistenttreeset,
+                clj.create_persistenttreeset,
+                true,
+            ),
+            EncoderTarget::Java(java) => self.construct(java.class_treeset, java.new_treeset),
+        }
+    }
+
+    /// The list must contain alternating keys and values.
+    pub fn into_sorted_map(self) -> Result<JObject<'a>> {
+        match &self.enc.target {
+            EncoderTarget::Clojure(clj) => self.create(
+                clj,
+                clj.class_persistenttreemap,
+                clj.create_persistenttreemap,
+                true,
+            ),
+            EncoderTarget::Java(java) => {
+                let env = &self.enc.com.env;
+                let map = env.auto_local(self.into_hashmap()?);
+                Ok(env.new_object_unchecked(
+                    java.class_treemap,
+                    java.new_treemap,
+                    &[map.as_obj().into()],
+                )?)
+            }
+        }
+    }
+
+    /// The list must contain a value and its metadata.
+    pub fn into_with_meta(self) -> Result<JObject<'a>> {
+        let env = &self.enc.com.env;
+        let get = |i: i32| -> Result<JObject<'a>> {
+            Ok(env
+                .call_method_unchecked(
+                    self.obj.as_obj(),
+                    self.enc.get_arraylist,
+                    JavaType::Object(String::new()),
+                    &[i.into()],
+                )?
+                .l()?)
+        };
+        let obj = get(0)?;
+        let meta = get(1)?;
+        self.enc.with_meta(obj, meta)
+    }
+
+    fn to_array(&self) -> Result<AutoLocal<'a, 'a>> {
+        Ok(self.enc.com.env.auto_local(
             self.enc
                 .com
                 .env
@@ -276,23 +1094,101 @@ impl<'a> ArrayList<'a> {
                     &[],
                 )?
                 .l()?,
-        );
-        Ok(self
-            .enc
-            .com
-            .env
-            .call_static_method_unchecked(
-                self.enc.com.class_persistenthashmap,
-                self.enc.create_persistenthashmap,
+        ))
+    }
+
+    /// The elements as an `Object[]`.
+    pub fn into_array(self) -> Result<JObject<'a>> {
+        Ok(self.to_array()?.forget())
+    }
+
+    /// The list must contain alternating field names (as strings) and
+    /// values.
+    pub fn into_bean(self, class: &str) -> Result<JObject<'a>> {
+        let env = &self.enc.com.env;
+        let arr = self.to_array()?;
+        let arr = arr.as_obj().into_inner();
+        let mut fields = Vec::new();
+        for i in (0..env.get_array_length(arr)?).step_by(2) {
+            let name = env.auto_local(env.get_object_array_element(arr, i)?);
+            let name = env.get_string(name.as_obj().into())?.into();
+            let val = env.auto_local(env.get_object_array_element(arr, i + 1)?);
+            fields.push((name, val));
+        }
+        self.enc.to_bean(&fields, class)
+    }
+
+    /// The list must contain alternating keys and values.
+    pub fn into_hashmap(self) -> Result<JObject<'a>> {
+        let arr = self.to_array()?;
+        let java = match &self.enc.target {
+            EncoderTarget::Clojure(clj) => {
+                return Ok(self
+                    .enc
+                    .com
+                    .env
+                    .call_static_method_unchecked(
+                        clj.class_persistenthashmap,
+                        clj.create_persistenthashmap,
+                        JavaType::Object(String::new()),
+                        &[arr.as_obj().into()],
+                    )?
+                    .l()?)
+            }
+            EncoderTarget::Java(java) => java,
+        };
+        let env = &self.enc.com.env;
+        let map =
+            env.new_object_unchecked(java.class_linkedhashmap, java.new_linkedhashmap, &[])?;
+        let len = env.get_array_length(arr.as_obj().into_inner())?;
+        for i in (0..len).step_by(2) {
+            let key = env.auto_local(env.get_object_array_element(arr.as_obj().into_inner(), i)?);
+            let val =
+                env.auto_local(env.get_object_array_element(arr.as_obj().into_inner(), i + 1)?);
+            let prev = env.call_method_unchecked(
+                map,
+                java.put_map,
                 JavaType::Object(String::new()),
-                &[arr.as_obj().into()],
-            )?
-            .l()?)
+                &[key.as_obj().into(), val.as_obj().into()],
+            )?;
+            env.auto_local(prev.l()?);
+        }
+        Ok(map)
     }
 }
 
+/// How closely the Java type of a value has to match the Rust type
+/// it is deserialized into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coercion {
+    /// Values must have exactly the Java type a default `Encoder`
+    /// produces for the Rust type (e.g. a `u8` must be a `Short`),
+    /// keywords are not accepted as strings, and tuples must have the
+    /// right length. Output of an `Encoder` with `canonical_numbers`
+    /// only decodes strictly into `i64` and `f64`.
+    Strict,
+    /// Integers and floats may be any width, keywords are accepted as
+    /// strings. This is the default.
+    #[default]
+    Standard,
+    /// Like `Standard`, but integers and floats may also be any
+    /// `java.lang.Number` (e.g. an integral `Double` or a `BigInt`) or
+    /// a numeric string, and booleans may be `"true"`/`"false"`.
+    Lenient,
+}
+
 pub struct Decoder<'a> {
     pub(crate) com: Common<'a>,
+    pub(crate) coercion: Coercion,
+    pub(crate) nil_punning: bool,
+    /// newtype struct name -> tag
+    pub(crate) tags: HashMap<String, String>,
+    /// struct name -> Java bean or record class name
+    pub(crate) beans: HashMap<String, String>,
+    /// class name -> how to read it
+    bean_accessors: RefCell<HashMap<String, Accessors>>,
+    /// buffers whose bytes have been lent out as `&'a [u8]`
+    lent: RefCell<Vec<GlobalRef>>,
     pub(crate) value_boolean: JMethodID<'a>,
     pub(crate) value_byte: JMethodID<'a>,
     pub(crate) value_integer: JMethodID<'a>,
@@ -301,51 +1197,199 @@ pub struct Decoder<'a> {
     pub(crate) value_float: JMethodID<'a>,
     pub(crate) value_double: JMethodID<'a>,
 
-    pub(crate) class_rt: JClass<'a>,
-    pub(crate) first_seq: JStaticMethodID<'a>,
-    pub(crate) next_seq: JStaticMethodID<'a>,
-
-    pub(crate) keyiterator_imapiterable: JMethodID<'a>,
-    pub(crate) valiterator_imapiterable: JMethodID<'a>,
+    /// `None` when decoding plain Java collections
+    clj: Option<ClojureDecoder<'a>>,
 
-    pub(crate) getname_keyword: JMethodID<'a>,
+    /// fallback for maps which aren't `IMapIterable`, e.g. sorted maps
+    /// and records
+    pub(crate) class_map: JClass<'a>,
+    pub(crate) entryset_map: JMethodID<'a>,
+    class_iterable: JClass<'a>,
+    pub(crate) iterator_iterable: JMethodID<'a>,
+    pub(crate) getkey_entry: JMethodID<'a>,
+    pub(crate) getvalue_entry: JMethodID<'a>,
 
     pub(crate) hasnext_iter: JMethodID<'a>,
     pub(crate) next_iter: JMethodID<'a>,
 
     /// a byte array
     pub(crate) class_bytes: JClass<'a>,
+
+    pub(crate) class_number: JClass<'a>,
+    doublevalue_number: JMethodID<'a>,
+    longvalue_number: JMethodID<'a>,
+    pub(crate) tostring_object: JMethodID<'a>,
+
+    class_optional: JClass<'a>,
+    ispresent_optional: JMethodID<'a>,
+    get_optional: JMethodID<'a>,
+    class_enum: JClass<'a>,
+    name_enum: JMethodID<'a>,
+}
+
+struct ClojureDecoder<'a> {
+    class_keyword: JClass<'a>,
+    getname_keyword: JMethodID<'a>,
+    class_symbol: JClass<'a>,
+
+    class_rt: JClass<'a>,
+    seq_seq: JStaticMethodID<'a>,
+    first_seq: JStaticMethodID<'a>,
+    next_seq: JStaticMethodID<'a>,
+
+    class_imapiterable: JClass<'a>,
+    keyiterator_imapiterable: JMethodID<'a>,
+    valiterator_imapiterable: JMethodID<'a>,
+
+    class_imeta: JClass<'a>,
+    meta_imeta: JMethodID<'a>,
+
+    class_taggedliteral: JClass<'a>,
+    tag_taggedliteral: JFieldID<'a>,
+    form_taggedliteral: JFieldID<'a>,
+
+    class_ifn: JClass<'a>,
+    applyto_ifn: JMethodID<'a>,
+}
+
+impl<'a> ClojureDecoder<'a> {
+    fn new(env: &JNIEnv<'a>) -> Result<Self> {
+        let class_keyword = env.find_class("clojure/lang/Keyword")?;
+        let class_rt = env.find_class("clojure/lang/RT")?;
+        let class_imapiterable = env.find_class("clojure/lang/IMapIterable")?;
+        let class_imeta = env.find_class("clojure/lang/IMeta")?;
+        let class_taggedliteral = env.find_class("clojure/lang/TaggedLiteral")?;
+        let class_ifn = env.find_class("clojure/lang/IFn")?;
+        Ok(Self {
+            getname_keyword: env.get_method_id(class_keyword, "getName", "()Ljava/lang/String;")?,
+            class_keyword,
+            class_symbol: env.find_class("clojure/lang/Symbol")?,
+
+            seq_seq: env.get_static_method_id(
+                class_rt,
+                "seq",
+                "(Ljava/lang/Object;)Lclojure/lang/ISeq;",
+            )?,
+            first_seq: env.get_static_method_id(
+                class_rt,
+                "first",
+                "(Ljava/lang/Object;)Ljava/lang/Object;",
+            )?,
+            next_seq: env.get_static_method_id(
+                class_rt,
+                "next",
+                "(Ljava/lang/Object;)Lclojure/lang/ISeq;",
+            )?,
+            class_rt,
+
+            keyiterator_imapiterable: env.get_method_id(
+                class_imapiterable,
+                "keyIterator",
+                "()Ljava/util/Iterator;",
+            )?,
+            valiterator_imapiterable: env.get_method_id(
+                class_imapiterable,
+                "valIterator",
+                "()Ljava/util/Iterator;",
+            )?,
+            class_imapiterable,
+
+            meta_imeta: env.get_method_id(
+                class_imeta,
+                "meta",
+                "()Lclojure/lang/IPersistentMap;",
+            )?,
+            class_imeta,
+
+            tag_taggedliteral: env.get_field_id(
+                class_taggedliteral,
+                "tag",
+                "Lclojure/lang/Symbol;",
+            )?,
+            form_taggedliteral: env.get_field_id(
+                class_taggedliteral,
+                "form",
+                "Ljava/lang/Object;",
+            )?,
+            class_taggedliteral,
+
+            applyto_ifn: env.get_method_id(
+                class_ifn,
+                "applyTo",
+                "(Lclojure/lang/ISeq;)Ljava/lang/Object;",
+            )?,
+            class_ifn,
+        })
+    }
+
+    fn pin(&mut self, env: &JNIEnv, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        pin!(
+            env,
+            globals,
+            self,
+            class_keyword,
+            class_symbol,
+            class_rt,
+            class_imapiterable,
+            class_imeta,
+            class_taggedliteral,
+            class_ifn,
+        );
+        Ok(())
+    }
 }
 
 macro_rules! decode {
     ($func:ident, $out:ident, $class:ident, $value_method:ident, $prim:ident, $code:ident) => {
         pub(crate) fn $func(&self, obj: JObject) -> Result<Option<$out>> {
-            if self
-                .com
-                .env
-                .is_instance_of(obj, self.com.$class)?
-            {
-                    Ok(Some(self
-                        .decode_prim(obj, self.$value_method, Primitive::$prim)?
-                        .$code()?))
+            if self.com.is_instance_of(obj, self.com.$class)? {
+                Ok(Some(
+                    self.decode_prim(obj, self.$value_method, Primitive::$prim)?
+                        .$code()?,
+                ))
             } else {
                 Ok(None)
             }
         }
-    }
+    };
 }
 
 impl<'a> Decoder<'a> {
+    /// A `Decoder` for Clojure data.
     pub fn new(env: JNIEnv<'a>) -> Result<Self> {
+        let clj = ClojureDecoder::new(&env)?;
+        Self::with_clojure(env, Some(clj))
+    }
+
+    /// A `Decoder` for plain Java collections (see
+    /// [`Encoder::java`](struct.Encoder.html#method.java)), which
+    /// doesn't need Clojure on the classpath. Sequences may be any
+    /// `Iterable` and maps any `java.util.Map`; strings are accepted
+    /// wherever keywords or symbols would be.
+    pub fn java(env: JNIEnv<'a>) -> Result<Self> {
+        Self::with_clojure(env, None)
+    }
+
+    fn with_clojure(env: JNIEnv<'a>, clj: Option<ClojureDecoder<'a>>) -> Result<Self> {
         let com = Common::new(env.clone())?;
-        let class_rt = env.find_class("clojure/lang/RT")?;
         let class_iter = env.find_class("java/util/Iterator")?;
         let hasnext_iter = env.get_method_id(class_iter, "hasNext", "()Z")?;
         let next_iter = com
             .env
             .get_method_id(class_iter, "next", "()Ljava/lang/Object;")?;
+        let class_map = env.find_class("java/util/Map")?;
+        let class_iterable = env.find_class("java/lang/Iterable")?;
+        let class_optional = env.find_class("java/util/Optional")?;
+        let class_enum = env.find_class("java/lang/Enum")?;
+        let class_number = env.find_class("java/lang/Number")?;
 
         Ok(Decoder {
+            coercion: Coercion::default(),
+            nil_punning: false,
+            tags: HashMap::new(),
+            beans: HashMap::new(),
+            bean_accessors: RefCell::new(HashMap::new()),
+            lent: RefCell::new(Vec::new()),
             value_boolean: com
                 .env
                 .get_method_id(com.class_boolean, "booleanValue", "()Z")?,
@@ -363,46 +1407,134 @@ impl<'a> Decoder<'a> {
                 .env
                 .get_method_id(com.class_integer, "intValue", "()I")?,
             value_long: env.get_method_id(com.class_long, "longValue", "()J")?,
-            // seq_rt: env.get_static_method_id(class_rt, "seq", "(Ljava/lang/Object;)Lclojure/lang/ISeq;")?,
-            first_seq: env.get_static_method_id(
-                class_rt,
-                "first",
-                "(Ljava/lang/Object;)Ljava/lang/Object;",
-            )?,
-            next_seq: env.get_static_method_id(
-                class_rt,
-                "next",
-                "(Ljava/lang/Object;)Lclojure/lang/ISeq;",
-            )?,
 
-            keyiterator_imapiterable: env.get_method_id(
-                com.class_imapiterable,
-                "keyIterator",
-                "()Ljava/util/Iterator;",
-            )?,
+            clj,
 
-            valiterator_imapiterable: env.get_method_id(
-                com.class_imapiterable,
-                "valIterator",
-                "()Ljava/util/Iterator;",
+            entryset_map: env.get_method_id(class_map, "entrySet", "()Ljava/util/Set;")?,
+            getkey_entry: env.get_method_id(
+                "java/util/Map$Entry",
+                "getKey",
+                "()Ljava/lang/Object;",
             )?,
-
-            getname_keyword: env.get_method_id(
-                com.class_keyword,
-                "getName",
-                "()Ljava/lang/String;",
+            getvalue_entry: env.get_method_id(
+                "java/util/Map$Entry",
+                "getValue",
+                "()Ljava/lang/Object;",
             )?,
+            iterator_iterable: env.get_method_id(
+                class_iterable,
+                "iterator",
+                "()Ljava/util/Iterator;",
+            )?,
+            class_iterable,
+            class_map,
 
             hasnext_iter,
             next_iter,
 
             class_bytes: env.find_class("[B")?,
-            class_rt,
-            // class_iseq,
+            doublevalue_number: env.get_method_id(class_number, "doubleValue", "()D")?,
+            longvalue_number: env.get_method_id(class_number, "longValue", "()J")?,
+            class_number,
+            tostring_object: env.get_method_id(
+                "java/lang/Object",
+                "toString",
+                "()Ljava/lang/String;",
+            )?,
+            ispresent_optional: env.get_method_id(class_optional, "isPresent", "()Z")?,
+            get_optional: env.get_method_id(class_optional, "get", "()Ljava/lang/Object;")?,
+            class_optional,
+            name_enum: env.get_method_id(class_enum, "name", "()Ljava/lang/String;")?,
+            class_enum,
             com,
         })
     }
 
+    fn pin(&mut self, globals: &mut Vec<GlobalRef>) -> Result<()> {
+        self.com.pin(globals)?;
+        let env = self.com.env.clone();
+        pin!(
+            env,
+            globals,
+            self,
+            class_map,
+            class_iterable,
+            class_bytes,
+            class_number,
+            class_optional,
+            class_enum,
+        );
+        match &mut self.clj {
+            Some(clj) => clj.pin(&env, globals),
+            None => Ok(()),
+        }
+    }
+
+    /// See [`Coercion`](enum.Coercion.html).
+    pub fn coercion(mut self, coercion: Coercion) -> Self {
+        self.coercion = coercion;
+        self
+    }
+
+    /// Accept `nil` wherever a collection or string is expected, and
+    /// treat it as an empty one, like Clojure does. A struct can then
+    /// be deserialized from `nil` if all its fields have defaults
+    /// (e.g. `#[serde(default)]`).
+    pub fn nil_punning(mut self, nil_punning: bool) -> Self {
+        self.nil_punning = nil_punning;
+        self
+    }
+
+    /// Expect the newtype struct `name` as a tagged literal with the
+    /// symbol `tag` (see
+    /// [`Encoder::tag_newtype`](struct.Encoder.html#method.tag_newtype)).
+    /// Literals with a different tag are rejected; untagged values are
+    /// accepted unless the coercion is `Strict`.
+    pub fn tag_newtype(mut self, name: &str, tag: &str) -> Self {
+        self.tags.insert(name.into(), tag.into());
+        self
+    }
+
+    /// Read the struct `name` from instances of the Java class `class`
+    /// using their getters (or, for a Java `record`, its component
+    /// accessors), matched to fields like in
+    /// [`Encoder::bean`](struct.Encoder.html#method.bean). Maps are
+    /// still accepted too.
+    pub fn bean(mut self, name: &str, class: &str) -> Self {
+        self.beans.insert(name.into(), class.into());
+        self
+    }
+
+    /// Whether this decodes Clojure data rather than plain Java.
+    pub(crate) fn is_clojure(&self) -> bool {
+        self.clj.is_some()
+    }
+
+    /// Reads `fields` out of `obj` if it is an instance of the bean or
+    /// record `class`.
+    pub(crate) fn decode_bean(
+        &'a self,
+        obj: JObject<'a>,
+        class: &str,
+        fields: &[&'static str],
+    ) -> Result<Option<Vec<(&'static str, AutoLocal<'a, 'a>)>>> {
+        let env = &self.com.env;
+        let mut accessors = self.bean_accessors.borrow_mut();
+        let accessors = match accessors.get(class) {
+            Some(found) => found,
+            None => {
+                let loaded = bean::load_class(env, class)?;
+                let found = Accessors::new(env, loaded, class)?;
+                accessors.entry(class.into()).or_insert(found)
+            }
+        };
+        let class = JClass::from(accessors.class.as_obj().into_inner());
+        if !self.com.is_instance_of(obj, class)? {
+            return Ok(None);
+        }
+        Ok(Some(accessors.read(env, obj, fields)?))
+    }
+
     fn decode_prim(
         &self,
         obj: JObject<'a>,
@@ -424,29 +1556,117 @@ impl<'a> Decoder<'a> {
     decode!(decode_i8, i8, class_byte, value_byte, Byte, b);
 
     pub(crate) fn decode_string(&self, obj: JObject) -> Result<Option<String>> {
-        if self.com.env.is_instance_of(obj, self.com.class_string)? {
+        if self.com.is_instance_of(obj, self.com.class_string)? {
             Ok(Some(self.com.env.get_string(obj.into())?.into()))
         } else {
             Ok(None)
         }
     }
 
+    fn call_tostring(&self, obj: JObject) -> Result<String> {
+        let s = self.com.env.auto_local(
+            self.com
+                .env
+                .call_method_unchecked(
+                    obj,
+                    self.tostring_object,
+                    JavaType::Object(String::new()),
+                    &[],
+                )?
+                .l()?,
+        );
+        Ok(self.com.env.get_string(s.as_obj().into())?.into())
+    }
+
+    /// The text of a `java.lang.Number` or a string, for lenient
+    /// decoding.
+    pub(crate) fn numeric_text(&self, obj: JObject) -> Result<Option<String>> {
+        if self.com.is_instance_of(obj, self.class_number)? {
+            Ok(Some(self.call_tostring(obj)?))
+        } else {
+            Ok(self.decode_string(obj)?.map(|s| s.trim().to_owned()))
+        }
+    }
+
+    /// Any number or numeric string with an integral value.
+    pub(crate) fn coerce_integer(&self, obj: JObject<'a>) -> Result<Option<i128>> {
+        let s = match self.numeric_text(obj)? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        // any integral type, however big
+        if let Ok(i) = s.parse() {
+            return Ok(Some(i));
+        }
+        if !self.com.is_instance_of(obj, self.class_number)? {
+            return Ok(s
+                .parse::<f64>()
+                .ok()
+                .filter(|f| f.fract() == 0.0 && f.abs() < 2f64.powi(64))
+                .map(|f| f as i128));
+        }
+        // any other number (an integral `Double`, `BigDecimal` or
+        // `Ratio`) if `longValue()` doesn't lose anything
+        let f = self
+            .decode_prim(obj, self.doublevalue_number, Primitive::Double)?
+            .d()?;
+        let i = self
+            .decode_prim(obj, self.longvalue_number, Primitive::Long)?
+            .j()?;
+        Ok(Some(i128::from(i)).filter(|_| f.fract() == 0.0 && i as f64 == f))
+    }
+
+    /// Any number or numeric string.
+    pub(crate) fn coerce_f64(&self, obj: JObject<'a>) -> Result<Option<f64>> {
+        if self.com.is_instance_of(obj, self.class_number)? {
+            let f = self.decode_prim(obj, self.doublevalue_number, Primitive::Double)?;
+            Ok(Some(f.d()?))
+        } else {
+            Ok(self.decode_string(obj)?.and_then(|s| s.trim().parse().ok()))
+        }
+    }
+
+    /// A boolean or the string `"true"` or `"false"`.
+    pub(crate) fn coerce_bool(&self, obj: JObject) -> Result<Option<bool>> {
+        if let Some(b) = self.decode_bool(obj)? {
+            return Ok(Some(b));
+        }
+        Ok(match self.decode_string(obj)?.as_deref() {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        })
+    }
+
     pub(crate) fn decode_bytes(&self, obj: JObject) -> Result<Option<Vec<u8>>> {
-        if self.com.env.is_instance_of(obj, self.class_bytes)? {
+        if self.com.is_instance_of(obj, self.class_bytes)? {
             Ok(Some(self.com.env.convert_byte_array(obj.into_inner())?))
         } else {
             Ok(None)
         }
     }
 
+    /// Keeps `obj` alive for as long as the `Decoder`, so that bytes
+    /// borrowed from it can be handed out as `&'a [u8]`.
+    pub(crate) fn lend(&self, obj: JObject) -> Result<()> {
+        let global = self.com.env.new_global_ref(obj)?;
+        self.lent.borrow_mut().push(global);
+        Ok(())
+    }
+
+    /// A keyword's name. Plain Java has no keywords.
     pub(crate) fn decode_keyword(&self, obj: JObject) -> Result<Option<String>> {
-        if self.com.env.is_instance_of(obj, self.com.class_keyword)? {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return Ok(None),
+        };
+        if self.com.is_instance_of(obj, clj.class_keyword)? {
             let name = self.com.env.auto_local(
                 self.com
                     .env
                     .call_method_unchecked(
                         obj,
-                        self.getname_keyword,
+                        clj.getname_keyword,
                         JavaType::Object(String::new()),
                         &[],
                     )?
@@ -459,21 +1679,288 @@ impl<'a> Decoder<'a> {
         }
     }
 
+    /// The name of a unit variant that isn't a keyword: a Java enum
+    /// constant, or for plain Java also a string.
+    pub(crate) fn decode_unit_variant(&self, obj: JObject) -> Result<Option<String>> {
+        if self.com.is_instance_of(obj, self.class_enum)? {
+            let name = self.com.env.auto_local(
+                self.com
+                    .env
+                    .call_method_unchecked(
+                        obj,
+                        self.name_enum,
+                        JavaType::Object(String::new()),
+                        &[],
+                    )?
+                    .l()?,
+            );
+            return Ok(Some(self.com.env.get_string(name.as_obj().into())?.into()));
+        }
+        match self.clj {
+            Some(_) => Ok(None),
+            None => self.decode_string(obj),
+        }
+    }
+
+    /// If `obj` is a `java.util.Optional`, its value (or `None` if it
+    /// is empty).
+    pub(crate) fn decode_optional(&self, obj: JObject<'a>) -> Result<Option<Option<JObject<'a>>>> {
+        if !self.com.is_instance_of(obj, self.class_optional)? {
+            return Ok(None);
+        }
+        let env = &self.com.env;
+        let present = env
+            .call_method_unchecked(
+                obj,
+                self.ispresent_optional,
+                JavaType::Primitive(Primitive::Boolean),
+                &[],
+            )?
+            .z()?;
+        if !present {
+            return Ok(Some(None));
+        }
+        let value = env
+            .call_method_unchecked(obj, self.get_optional, JavaType::Object(String::new()), &[])?
+            .l()?;
+        Ok(Some(Some(value)))
+    }
+
+    /// A keyword's name, including its namespace if it has one (or,
+    /// for plain Java, a string).
+    pub(crate) fn decode_qualified_keyword(&self, obj: JObject) -> Result<Option<String>> {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return self.decode_string(obj),
+        };
+        if self.com.is_instance_of(obj, clj.class_keyword)? {
+            // toString gives ":ns/name"
+            Ok(Some(self.call_tostring(obj)?[1..].to_owned()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// A symbol's name, including its namespace if it has one (or, for
+    /// plain Java, a string).
+    pub(crate) fn decode_symbol(&self, obj: JObject) -> Result<Option<String>> {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return self.decode_string(obj),
+        };
+        if self.com.is_instance_of(obj, clj.class_symbol)? {
+            Ok(Some(self.call_tostring(obj)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The tag and form of a tagged literal.
+    pub(crate) fn decode_tagged(&self, obj: JObject<'a>) -> Result<Option<(String, JObject<'a>)>> {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return Ok(None),
+        };
+        if !self.com.is_instance_of(obj, clj.class_taggedliteral)? {
+            return Ok(None);
+        }
+        let env = &self.com.env;
+        let tag = env.auto_local(
+            env.get_field_unchecked(obj, clj.tag_taggedliteral, JavaType::Object(String::new()))?
+                .l()?,
+        );
+        let tag = self.call_tostring(tag.as_obj())?;
+        let form = env
+            .get_field_unchecked(obj, clj.form_taggedliteral, JavaType::Object(String::new()))?
+            .l()?;
+        Ok(Some((tag, form)))
+    }
+
+    /// Whether `obj` is a Clojure fn (or anything else callable).
+    pub(crate) fn is_fn(&self, obj: JObject<'a>) -> Result<bool> {
+        match &self.clj {
+            Some(clj) => self.com.is_instance_of(obj, clj.class_ifn),
+            None => Ok(false),
+        }
+    }
+
+    /// Calls the fn `f` with the elements of the sequence `args`.
+    /// This method will invalidate the local ref `args`!
+    pub(crate) fn apply(&self, f: JObject<'a>, args: JObject<'a>) -> Result<JObject<'a>> {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return Err(Error::Message("fns can only be called with Clojure".into())),
+        };
+        let env = &self.com.env;
+        let args = env.auto_local(args);
+        let seq = env.auto_local(
+            env.call_static_method_unchecked(
+                clj.class_rt,
+                clj.seq_seq,
+                JavaType::Object(String::new()),
+                &[args.as_obj().into()],
+            )?
+            .l()?,
+        );
+        Ok(env
+            .call_method_unchecked(
+                f,
+                clj.applyto_ifn,
+                JavaType::Object(String::new()),
+                &[seq.as_obj().into()],
+            )?
+            .l()?)
+    }
+
+    /// The metadata of `obj`, or `nil`.
+    pub(crate) fn decode_meta(&self, obj: JObject<'a>) -> Result<JObject<'a>> {
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => return Ok(JObject::null()),
+        };
+        if self.com.is_instance_of(obj, clj.class_imeta)? {
+            Ok(self
+                .com
+                .env
+                .call_method_unchecked(obj, clj.meta_imeta, JavaType::Object(String::new()), &[])?
+                .l()?)
+        } else {
+            Ok(JObject::null())
+        }
+    }
+
+    /// Starts iterating over the sequence `obj`: for Clojure that's
+    /// `RT.seq(obj)` (which is `nil` for an empty collection), for
+    /// plain Java an `Iterator`. `nil` is an empty sequence.
+    pub(crate) fn start_seq(&'a self, obj: AutoLocal<'a, 'a>) -> Result<AutoLocal<'a, 'a>> {
+        if obj.as_obj().is_null() {
+            return Ok(obj);
+        }
+        if let Some(clj) = &self.clj {
+            let seq = self
+                .com
+                .env
+                .call_static_method_unchecked(
+                    clj.class_rt,
+                    clj.seq_seq,
+                    JavaType::Object(String::new()),
+                    &[obj.as_obj().into()],
+                )
+                .map_err(Error::from);
+            return match catch_exception(&self.com.env, seq) {
+                Ok(seq) => Ok(self.com.env.auto_local(seq.l()?)),
+                // "Don't know how to create ISeq from: ..."
+                Err(Error::Exception(ref desc))
+                    if desc.starts_with("java.lang.IllegalArgumentException") =>
+                {
+                    Err(Error::ExpectedArray)
+                }
+                Err(e) => Err(e),
+            };
+        }
+        if !self.com.is_instance_of(obj.as_obj(), self.class_iterable)? {
+            return Err(Error::ExpectedArray);
+        }
+        Ok(self.com.env.auto_local(
+            self.com
+                .env
+                .call_method_unchecked(
+                    obj.as_obj(),
+                    self.iterator_iterable,
+                    JavaType::Object(String::new()),
+                    &[],
+                )?
+                .l()?,
+        ))
+    }
+
+    /// Whether the sequence started by `start_seq` has no more
+    /// elements.
+    pub(crate) fn seq_done(&self, seq: &AutoLocal<'a, 'a>) -> Result<bool> {
+        if self.is_clojure() || seq.as_obj().is_null() {
+            return Ok(seq.as_obj().is_null());
+        }
+        Ok(!self
+            .com
+            .env
+            .call_method_unchecked(
+                seq.as_obj(),
+                self.hasnext_iter,
+                JavaType::Primitive(Primitive::Boolean),
+                &[],
+            )?
+            .z()?)
+    }
+
+    /// The next element of the sequence started by `start_seq`.
+    pub(crate) fn next_element(
+        &'a self,
+        seq: &mut AutoLocal<'a, 'a>,
+    ) -> Result<Option<AutoLocal<'a, 'a>>> {
+        if self.seq_done(seq)? {
+            return Ok(None);
+        }
+        let env = &self.com.env;
+        let clj = match &self.clj {
+            Some(clj) => clj,
+            None => {
+                return Ok(Some(
+                    env.auto_local(
+                        env.call_method_unchecked(
+                            seq.as_obj(),
+                            self.next_iter,
+                            JavaType::Object(String::new()),
+                            &[],
+                        )?
+                        .l()?,
+                    ),
+                ))
+            }
+        };
+        let first = env.auto_local(
+            env.call_static_method_unchecked(
+                clj.class_rt,
+                clj.first_seq,
+                JavaType::Object(String::new()),
+                &[seq.as_obj().into()],
+            )?
+            .l()?,
+        );
+        *seq = env.auto_local(
+            env.call_static_method_unchecked(
+                clj.class_rt,
+                clj.next_seq,
+                JavaType::Object(String::new()),
+                &[seq.as_obj().into()],
+            )?
+            .l()?,
+        );
+        Ok(Some(first))
+    }
+
+    /// Returns iterators over the keys and values of a map. If the
+    /// bool is true, both iterate over its `Map.Entry`s instead.
     pub(crate) fn map_to_iters(
         &self,
         obj: AutoLocal<'a, 'a>,
-    ) -> Result<Option<(AutoLocal, AutoLocal)>> {
-        if self
-            .com
-            .env
-            .is_instance_of(obj.as_obj(), self.com.class_imapiterable)?
-        {
+    ) -> Result<Option<(AutoLocal<'a, '_>, AutoLocal<'a, '_>, bool)>> {
+        let imapiterable = match &self.clj {
+            Some(clj)
+                if self
+                    .com
+                    .is_instance_of(obj.as_obj(), clj.class_imapiterable)? =>
+            {
+                Some(clj)
+            }
+            _ => None,
+        };
+        if let Some(clj) = imapiterable {
             let key_iter = self.com.env.auto_local(
                 self.com
                     .env
                     .call_method_unchecked(
                         obj.as_obj(),
-                        self.keyiterator_imapiterable,
+                        clj.keyiterator_imapiterable,
                         JavaType::Object(String::new()),
                         &[obj.as_obj().into()],
                     )?
@@ -485,14 +1972,39 @@ impl<'a> Decoder<'a> {
                     .env
                     .call_method_unchecked(
                         obj.as_obj(),
-                        self.valiterator_imapiterable,
+                        clj.valiterator_imapiterable,
                         JavaType::Object(String::new()),
                         &[obj.as_obj().into()],
                     )?
                     .l()?,
             );
 
-            Ok(Some((key_iter, val_iter)))
+            Ok(Some((key_iter, val_iter, false)))
+        } else if self.com.is_instance_of(obj.as_obj(), self.class_map)? {
+            // Records build a new set for each `keySet()`/`values()`, so
+            // iterate over the same entry set twice to keep them in step.
+            let env = &self.com.env;
+            let entries = env.auto_local(
+                env.call_method_unchecked(
+                    obj.as_obj(),
+                    self.entryset_map,
+                    JavaType::Object(String::new()),
+                    &[],
+                )?
+                .l()?,
+            );
+            let iter = || -> Result<AutoLocal<'a, '_>> {
+                Ok(env.auto_local(
+                    env.call_method_unchecked(
+                        entries.as_obj(),
+                        self.iterator_iterable,
+                        JavaType::Object(String::new()),
+                        &[],
+                    )?
+                    .l()?,
+                ))
+            };
+            Ok(Some((iter()?, iter()?, true)))
         } else {
             Ok(None)
         }
@@ -1,14 +1,28 @@
+use serde::de::value::{MapDeserializer, SeqDeserializer};
 use serde::de::{
     self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
     Visitor,
 };
 use serde::Deserialize;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::iter;
+use std::marker::PhantomData;
 
-use jni::objects::{AutoLocal, JObject};
+use jni::objects::{AutoLocal, JMethodID, JObject};
 use jni::signature::{JavaType, Primitive};
 
-use crate::convert::Decoder;
+use crate::buffer::{self, Kind};
+use crate::convert::{Coercion, Decoder};
 use crate::error::{Error, Result};
+use crate::handle;
+use crate::marker;
+#[cfg(any(feature = "num-bigint", feature = "rust_decimal"))]
+use crate::num;
+#[cfg(feature = "chrono")]
+use crate::time;
+#[cfg(feature = "uuid")]
+use crate::uuids;
 
 pub struct Deserializer<'de> {
     dec: &'de Decoder<'de>,
@@ -29,8 +43,165 @@ where
     T::deserialize(deserializer)
 }
 
+/// Iterates over the elements of the collection or seq `obj`,
+/// deserializing each one as it is reached, like `from_object::<Vec<T>>`
+/// without collecting them:
+///
+/// ```no_run
+/// # use jni::{JNIEnv, objects::{JClass, JObject}, sys::jlong};
+/// # use serde_clj::Decoder;
+/// #[no_mangle]
+/// pub extern "system" fn Java_Foo_sum(env: JNIEnv, _: JClass, xs: JObject) -> jlong {
+///     let dec = Decoder::new(env).unwrap();
+///     serde_clj::iter_from_object::<i64>(&dec, xs).map(Result::unwrap).sum()
+/// }
+/// ```
+///
+/// Only the current element and position are referenced, and each
+/// element's local references are deleted once it has been
+/// deserialized, so a huge (or lazy, even infinite) seq can be consumed
+/// in constant memory, as long as nothing else holds on to its head.
+/// An element which fails to deserialize yields an error and iteration
+/// continues; an error walking the seq itself (or `nil` without
+/// `Decoder::nil_punning`) ends it.
+pub fn iter_from_object<'a, T>(dec: &'a Decoder<'a>, obj: JObject<'a>) -> SeqIter<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    let seq = if !dec.nil_punning && is_null(obj) {
+        Err(Error::ExpectedArray)
+    } else {
+        dec.start_seq(dec.com.env.auto_local(obj))
+    };
+    let (seq, error) = match seq {
+        Ok(seq) => (Some(seq), None),
+        Err(e) => (None, Some(e)),
+    };
+    SeqIter {
+        dec,
+        seq,
+        error,
+        marker: PhantomData,
+    }
+}
+
+/// The iterator returned by `iter_from_object`.
+pub struct SeqIter<'a, T> {
+    dec: &'a Decoder<'a>,
+    /// `None` once done
+    seq: Option<AutoLocal<'a, 'a>>,
+    /// from starting the seq, returned by the first `next`
+    error: Option<Error>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Iterator for SeqIter<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        match self.dec.next_element(self.seq.as_mut()?) {
+            Ok(Some(elem)) => Some(T::deserialize(Deserializer::from_object(self.dec, elem))),
+            Ok(None) => {
+                self.seq = None;
+                None
+            }
+            Err(e) => {
+                self.seq = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 fn is_null<'a>(obj: JObject<'a>) -> bool {
-    obj.clone().into_inner() == JObject::null().into_inner()
+    obj.into_inner() == JObject::null().into_inner()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Int {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Tries the boxed integer type `preferred` first, then the other
+    /// widths. In strict mode only `strict` is accepted, in lenient
+    /// mode anything `Decoder::coerce_integer` understands.
+    fn deserialize_integer<V>(self, visitor: V, preferred: Int, strict: Int) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let obj = self.obj.as_obj();
+        let order = [preferred, Int::I8, Int::I16, Int::I32, Int::I64];
+        let order: &[Int] = if self.dec.coercion == Coercion::Strict {
+            &[strict]
+        } else {
+            &order
+        };
+        for (i, &int) in order.iter().enumerate() {
+            match int {
+                _ if i > 0 && int == preferred => {}
+                Int::I8 => {
+                    if let Some(v) = self.dec.decode_i8(obj)? {
+                        return visitor.visit_i8(v);
+                    }
+                }
+                Int::I16 => {
+                    if let Some(v) = self.dec.decode_i16(obj)? {
+                        return visitor.visit_i16(v);
+                    }
+                }
+                Int::I32 => {
+                    if let Some(v) = self.dec.decode_i32(obj)? {
+                        return visitor.visit_i32(v);
+                    }
+                }
+                Int::I64 => {
+                    if let Some(v) = self.dec.decode_i64(obj)? {
+                        return visitor.visit_i64(v);
+                    }
+                }
+            }
+        }
+        if self.dec.coercion == Coercion::Lenient {
+            if let Some(v) = self.dec.coerce_integer(obj)? {
+                if let Ok(v) = i64::try_from(v) {
+                    return visitor.visit_i64(v);
+                } else if let Ok(v) = u64::try_from(v) {
+                    return visitor.visit_u64(v);
+                }
+            }
+        }
+        Err(Error::ExpectedInteger)
+    }
+
+    /// A newtype struct configured with `Decoder::tag_newtype`.
+    fn deserialize_tagged<V>(self, tag: &str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.dec.decode_tagged(self.obj.as_obj())? {
+            Some((actual, form)) if actual == tag => {
+                let env = &self.dec.com.env;
+                visitor
+                    .visit_newtype_struct(Deserializer::from_object(self.dec, env.auto_local(form)))
+            }
+            Some((actual, _)) => Err(Error::Message(format!(
+                "expected tag #{}, got #{}",
+                tag, actual
+            ))),
+            None if self.dec.coercion == Coercion::Strict => Err(Error::ExpectedTaggedLiteral),
+            None => visitor.visit_newtype_struct(self),
+        }
+    }
 }
 
 // based on https://serde.rs/impl-deserializer.html
@@ -49,7 +220,12 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if let Some(b) = self.dec.decode_bool(self.obj.as_obj())? {
+        let b = if self.dec.coercion == Coercion::Lenient {
+            self.dec.coerce_bool(self.obj.as_obj())?
+        } else {
+            self.dec.decode_bool(self.obj.as_obj())?
+        };
+        if let Some(b) = b {
             visitor.visit_bool(b)
         } else {
             Err(Error::ExpectedBoolean)
@@ -60,96 +236,58 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if let Some(v) = self.dec.decode_i8(self.obj.as_obj())? {
-            visitor.visit_i8(v)
-        } else if let Some(v) = self.dec.decode_i16(self.obj.as_obj())? {
-            visitor.visit_i16(v)
-        } else if let Some(v) = self.dec.decode_i32(self.obj.as_obj())? {
-            visitor.visit_i32(v)
-        } else if let Some(v) = self.dec.decode_i64(self.obj.as_obj())? {
-            visitor.visit_i64(v)
-        } else {
-            Err(Error::ExpectedInteger)
-        }
+        self.deserialize_integer(visitor, Int::I8, Int::I8)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Some(v) = self.dec.decode_i16(self.obj.as_obj())? {
-            visitor.visit_i16(v)
-        } else if let Some(v) = self.dec.decode_i8(self.obj.as_obj())? {
-            visitor.visit_i8(v)
-        } else if let Some(v) = self.dec.decode_i32(self.obj.as_obj())? {
-            visitor.visit_i32(v)
-        } else if let Some(v) = self.dec.decode_i64(self.obj.as_obj())? {
-            visitor.visit_i64(v)
-        } else {
-            Err(Error::ExpectedInteger)
-        }
+        self.deserialize_integer(visitor, Int::I16, Int::I16)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Some(v) = self.dec.decode_i32(self.obj.as_obj())? {
-            visitor.visit_i32(v)
-        } else if let Some(v) = self.dec.decode_i8(self.obj.as_obj())? {
-            visitor.visit_i8(v)
-        } else if let Some(v) = self.dec.decode_i16(self.obj.as_obj())? {
-            visitor.visit_i16(v)
-        } else if let Some(v) = self.dec.decode_i64(self.obj.as_obj())? {
-            visitor.visit_i64(v)
-        } else {
-            Err(Error::ExpectedInteger)
-        }
+        self.deserialize_integer(visitor, Int::I32, Int::I32)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Some(v) = self.dec.decode_i64(self.obj.as_obj())? {
-            visitor.visit_i64(v)
-        } else if let Some(v) = self.dec.decode_i8(self.obj.as_obj())? {
-            visitor.visit_i8(v)
-        } else if let Some(v) = self.dec.decode_i16(self.obj.as_obj())? {
-            visitor.visit_i16(v)
-        } else if let Some(v) = self.dec.decode_i32(self.obj.as_obj())? {
-            visitor.visit_i32(v)
-        } else {
-            Err(Error::ExpectedInteger)
-        }
+        self.deserialize_integer(visitor, Int::I64, Int::I64)
     }
 
+    // unsigned ints are encoded as the next biggest type (see ser.rs)
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Deserializer::deserialize_i8(self, visitor)
+        self.deserialize_integer(visitor, Int::I8, Int::I16)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Deserializer::deserialize_i16(self, visitor)
+        self.deserialize_integer(visitor, Int::I16, Int::I32)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Deserializer::deserialize_i32(self, visitor)
+        self.deserialize_integer(visitor, Int::I32, Int::I64)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Deserializer::deserialize_i64(self, visitor)
+        self.deserialize_integer(visitor, Int::I64, Int::I64)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
@@ -158,10 +296,10 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     {
         if let Some(f) = self.dec.decode_f32(self.obj.as_obj())? {
             visitor.visit_f32(f)
-        } else if let Some(f) = self.dec.decode_f64(self.obj.as_obj())? {
-            visitor.visit_f64(f)
-        } else {
+        } else if self.dec.coercion == Coercion::Strict {
             Err(Error::ExpectedFloat)
+        } else {
+            self.deserialize_f64(visitor)
         }
     }
 
@@ -171,8 +309,15 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     {
         if let Some(f) = self.dec.decode_f64(self.obj.as_obj())? {
             visitor.visit_f64(f)
+        } else if self.dec.coercion == Coercion::Strict {
+            Err(Error::ExpectedFloat)
         } else if let Some(f) = self.dec.decode_f32(self.obj.as_obj())? {
             visitor.visit_f32(f)
+        } else if self.dec.coercion == Coercion::Lenient {
+            match self.dec.coerce_f64(self.obj.as_obj())? {
+                Some(f) => visitor.visit_f64(f),
+                None => Err(Error::ExpectedFloat),
+            }
         } else {
             Err(Error::ExpectedFloat)
         }
@@ -207,10 +352,12 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     {
         if let Some(s) = self.dec.decode_string(self.obj.as_obj())? {
             visitor.visit_string(s)
-        } else if let Some(s) = self.dec.decode_keyword(self.obj.as_obj())? {
-            visitor.visit_string(s)
-        } else {
+        } else if self.dec.nil_punning && is_null(self.obj.as_obj()) {
+            visitor.visit_str("")
+        } else if self.dec.coercion == Coercion::Strict {
             Err(Error::ExpectedString)
+        } else {
+            self.deserialize_identifier(visitor)
         }
     }
 
@@ -219,7 +366,11 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
         V: Visitor<'de>,
     {
         if let Some(bs) = self.dec.decode_bytes(self.obj.as_obj())? {
-            visitor.visit_bytes(&bs)
+            visitor.visit_byte_buf(bs)
+        } else if self.dec.nil_punning && is_null(self.obj.as_obj()) {
+            visitor.visit_byte_buf(Vec::new())
+        } else if let Some(bs) = buffer::from_java(self.dec, self.obj.as_obj(), Kind::Byte)? {
+            visitor.visit_byte_buf(bs.into_owned())
         } else {
             Err(Error::ExpectedBytes)
         }
@@ -229,7 +380,23 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        Deserializer::deserialize_byte_buf(self, visitor)
+        if let Some(bs) = self.dec.decode_bytes(self.obj.as_obj())? {
+            visitor.visit_bytes(&bs)
+        } else if self.dec.nil_punning && is_null(self.obj.as_obj()) {
+            visitor.visit_bytes(&[])
+        } else if let Some(bs) = buffer::from_java(self.dec, self.obj.as_obj(), Kind::Byte)? {
+            match bs {
+                Cow::Borrowed(bs) => {
+                    // the bytes belong to the buffer, which must outlive
+                    // our local ref to it
+                    self.dec.lend(self.obj.as_obj())?;
+                    visitor.visit_borrowed_bytes(bs)
+                }
+                Cow::Owned(bs) => visitor.visit_byte_buf(bs),
+            }
+        } else {
+            Err(Error::ExpectedBytes)
+        }
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -237,9 +404,15 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
         V: Visitor<'de>,
     {
         if is_null(self.obj.as_obj()) {
-            visitor.visit_none()
-        } else {
-            visitor.visit_some(self)
+            return visitor.visit_none();
+        }
+        match self.dec.decode_optional(self.obj.as_obj())? {
+            Some(Some(value)) => visitor.visit_some(Deserializer::from_object(
+                self.dec,
+                self.dec.com.env.auto_local(value),
+            )),
+            Some(None) => visitor.visit_none(),
+            None => visitor.visit_some(self),
         }
     }
 
@@ -261,51 +434,200 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        match name {
+            marker::DIRECT_BUFFER => {
+                match buffer::from_java(self.dec, self.obj.as_obj(), marker::take_for(name)?)? {
+                    Some(bs) => visitor.visit_bytes(&bs),
+                    None => Err(Error::ExpectedDirectBuffer),
+                }
+            }
+            marker::PRIMITIVE_ARRAY => {
+                let kind = marker::take_for(name)?;
+                match buffer::array_from_java(self.dec, self.obj.as_obj(), kind)? {
+                    Some(bs) => visitor.visit_bytes(&bs),
+                    None => visitor.visit_newtype_struct(self),
+                }
+            }
+            marker::HANDLE => match handle::from_java(self.dec, self.obj.as_obj())? {
+                Some(import) => {
+                    marker::stash(import);
+                    visitor.visit_unit()
+                }
+                None => Err(Error::ExpectedHandle),
+            },
+            marker::WITH_META => {
+                let meta = self.dec.decode_meta(self.obj.as_obj())?;
+                let meta = self.dec.com.env.auto_local(meta);
+                visitor.visit_seq(Parts {
+                    dec: self.dec,
+                    parts: vec![self.obj, meta].into_iter(),
+                })
+            }
+            #[cfg(feature = "chrono")]
+            marker::INSTANT | marker::INST | marker::LOCAL_DATE => {
+                match time::moment_from_java(self.dec, self.obj.as_obj())? {
+                    Some(m) => {
+                        marker::stash(m);
+                        visitor.visit_unit()
+                    }
+                    None => Err(Error::ExpectedTimestamp),
+                }
+            }
+            #[cfg(feature = "chrono")]
+            marker::DURATION => match time::span_from_java(self.dec, self.obj.as_obj())? {
+                Some(span) => {
+                    marker::stash(span);
+                    visitor.visit_unit()
+                }
+                None => Err(Error::ExpectedDuration),
+            },
+            #[cfg(feature = "uuid")]
+            marker::UUID => match uuids::from_java(self.dec, self.obj.as_obj())? {
+                Some(id) => {
+                    marker::stash(id);
+                    visitor.visit_unit()
+                }
+                None if self.dec.decode_string(self.obj.as_obj())?.is_some() => {
+                    visitor.visit_newtype_struct(self)
+                }
+                None => Err(Error::ExpectedUuid),
+            },
+            #[cfg(feature = "num-bigint")]
+            marker::BIGINT => match num::bigint_from_java(self.dec, self.obj.as_obj())? {
+                Some(n) => {
+                    marker::stash(n);
+                    visitor.visit_unit()
+                }
+                None => Err(Error::ExpectedInteger),
+            },
+            #[cfg(feature = "num-rational")]
+            marker::RATIO => match num::ratio_from_java(self.dec, self.obj.as_obj())? {
+                Some(r) => {
+                    marker::stash(r);
+                    visitor.visit_unit()
+                }
+                None => Err(Error::ExpectedRatio),
+            },
+            #[cfg(feature = "rust_decimal")]
+            marker::DECIMAL => match num::decimal_from_java(self.dec, self.obj.as_obj())? {
+                Some(d) => {
+                    marker::stash(d);
+                    visitor.visit_unit()
+                }
+                None => Err(Error::ExpectedDecimal),
+            },
+            // a matrix in either form, or one of its elements
+            #[cfg(feature = "ndarray")]
+            marker::NDARRAY => {
+                let obj = self.obj.as_obj();
+                if self.dec.com.is_instance_of(obj, self.dec.class_map)? {
+                    self.deserialize_map(visitor)
+                } else if let Some(v) = self.dec.decode_f64(obj)? {
+                    visitor.visit_f64(v)
+                } else if let Some(v) = self.dec.decode_f32(obj)? {
+                    visitor.visit_f64(v.into())
+                } else if self.dec.com.is_instance_of(obj, self.dec.class_number)? {
+                    self.deserialize_i64(visitor)
+                } else {
+                    self.deserialize_seq(visitor)
+                }
+            }
+            marker::RAW => {
+                marker::stash(self.dec.com.env.new_global_ref(self.obj.as_obj())?);
+                visitor.visit_unit()
+            }
+            marker::FN => {
+                if !self.dec.is_fn(self.obj.as_obj())? {
+                    return Err(Error::ExpectedFn);
+                }
+                marker::stash(self.dec.com.env.new_global_ref(self.obj.as_obj())?);
+                visitor.visit_unit()
+            }
+            marker::KEYWORD => match self.dec.decode_qualified_keyword(self.obj.as_obj())? {
+                Some(s) => visitor.visit_string(s),
+                None => Err(Error::ExpectedKeyword),
+            },
+            marker::SYMBOL => match self.dec.decode_symbol(self.obj.as_obj())? {
+                Some(s) => visitor.visit_string(s),
+                None => Err(Error::ExpectedSymbol),
+            },
+            _ => match self.dec.tags.get(name) {
+                Some(tag) => self.deserialize_tagged(tag, visitor),
+                None => visitor.visit_newtype_struct(self),
+            },
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        // Seq treats nil as empty, but that is only what we want when
+        // nil punning
+        if !self.dec.nil_punning && is_null(self.obj.as_obj()) {
+            return Err(Error::ExpectedArray);
+        }
+        // a `Vec<u8>` asks for a seq rather than bytes
+        if let Some(bs) = buffer::from_java(self.dec, self.obj.as_obj(), Kind::Byte)? {
+            return visitor.visit_seq(SeqDeserializer::<_, Error>::new(bs.iter().copied()));
+        }
         visitor.visit_seq(Seq {
             dec: self.dec,
-            seq: self.obj,
+            seq: self.dec.start_seq(self.obj)?,
         })
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        if self.dec.coercion != Coercion::Strict || is_null(self.obj.as_obj()) {
+            return self.deserialize_seq(visitor);
+        }
+        let mut seq = Seq {
+            dec: self.dec,
+            seq: self.dec.start_seq(self.obj)?,
+        };
+        let res = visitor.visit_seq(&mut seq)?;
+        if self.dec.seq_done(&seq.seq)? {
+            Ok(res)
+        } else {
+            // the rest of the seq may be long (or infinite), so don't count it
+            Err(Error::Message(format!(
+                "too many elements, expected a tuple of size {}",
+                len
+            )))
+        }
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Some((key_iter, val_iter)) = self.dec.map_to_iters(self.obj)? {
+        if self.dec.nil_punning && is_null(self.obj.as_obj()) {
+            visitor.visit_map(MapDeserializer::new(iter::empty::<((), ())>()))
+        } else if let Some((key_iter, val_iter, entries)) = self.dec.map_to_iters(self.obj)? {
             visitor.visit_map(Map {
                 dec: self.dec,
                 key_iter,
                 val_iter,
+                entries,
             })
         } else {
             Err(Error::ExpectedMap)
@@ -314,13 +636,22 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
+        name: &'static str,
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if let Some(class) = self.dec.beans.get(name) {
+            if let Some(fields) = self.dec.decode_bean(self.obj.as_obj(), class, fields)? {
+                return visitor.visit_map(Fields {
+                    dec: self.dec,
+                    fields: fields.into_iter(),
+                    value: None,
+                });
+            }
+        }
         self.deserialize_map(visitor)
     }
 
@@ -333,14 +664,17 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        // test if it's a bare keyword (unit variant)
+        // test if it's a bare keyword or enum constant (unit variant)
         if let Some(s) = self.dec.decode_keyword(self.obj.as_obj())? {
             visitor.visit_enum(s.into_deserializer())
-        } else if let Some((key_iter, val_iter)) = self.dec.map_to_iters(self.obj)? {
+        } else if let Some(s) = self.dec.decode_unit_variant(self.obj.as_obj())? {
+            visitor.visit_enum(s.into_deserializer())
+        } else if let Some((key_iter, val_iter, entries)) = self.dec.map_to_iters(self.obj)? {
             visitor.visit_enum(Map {
                 dec: self.dec,
                 key_iter,
                 val_iter,
+                entries,
             })
         } else {
             Err(Error::ExpectedMap)
@@ -351,7 +685,14 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        // struct fields are keywords, even in strict mode
+        if let Some(s) = self.dec.decode_keyword(self.obj.as_obj())? {
+            visitor.visit_string(s)
+        } else if let Some(s) = self.dec.decode_string(self.obj.as_obj())? {
+            visitor.visit_string(s)
+        } else {
+            Err(Error::ExpectedString)
+        }
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
@@ -367,44 +708,73 @@ struct Seq<'de> {
     seq: AutoLocal<'de, 'de>,
 }
 
-impl<'de, 'a> SeqAccess<'de> for Seq<'de> {
+impl<'de> SeqAccess<'de> for Seq<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: DeserializeSeed<'de>,
     {
-        if is_null(self.seq.as_obj()) {
-            return Ok(None);
+        match self.dec.next_element(&mut self.seq)? {
+            Some(elem) => Ok(Some(
+                seed.deserialize(Deserializer::from_object(self.dec, elem))?,
+            )),
+            None => Ok(None),
         }
-        let first = self.dec.com.env.auto_local(
-            self.dec
-                .com
-                .env
-                .call_static_method_unchecked(
-                    self.dec.class_rt,
-                    self.dec.first_seq,
-                    JavaType::Object(String::new()),
-                    &[self.seq.as_obj().into()],
-                )?
-                .l()?,
-        );
+    }
+}
 
-        self.seq = self.dec.com.env.auto_local(
-            self.dec
-                .com
-                .env
-                .call_static_method_unchecked(
-                    self.dec.class_rt,
-                    self.dec.next_seq,
-                    JavaType::Object(String::new()),
-                    &[self.seq.as_obj().into()],
-                )?
-                .l()?,
-        );
-        Ok(Some(
-            seed.deserialize(Deserializer::from_object(self.dec, first))?,
-        ))
+/// A fixed sequence of objects.
+struct Parts<'de> {
+    dec: &'de Decoder<'de>,
+    parts: std::vec::IntoIter<AutoLocal<'de, 'de>>,
+}
+
+impl<'de> SeqAccess<'de> for Parts<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.parts.next() {
+            Some(obj) => Ok(Some(
+                seed.deserialize(Deserializer::from_object(self.dec, obj))?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Struct fields read out of a Java bean.
+struct Fields<'de> {
+    dec: &'de Decoder<'de>,
+    fields: std::vec::IntoIter<(&'static str, AutoLocal<'de, 'de>)>,
+    value: Option<AutoLocal<'de, 'de>>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.fields.next() {
+            Some((name, value)) => {
+                self.value = Some(value);
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self.value.take().expect("next_value before next_key");
+        seed.deserialize(Deserializer::from_object(self.dec, value))
     }
 }
 
@@ -412,6 +782,8 @@ struct Map<'de> {
     dec: &'de Decoder<'de>,
     key_iter: AutoLocal<'de, 'de>,
     val_iter: AutoLocal<'de, 'de>,
+    /// the iterators return `Map.Entry`s
+    entries: bool,
 }
 
 impl<'de> Map<'de> {
@@ -442,10 +814,14 @@ impl<'de> Map<'de> {
                 )?
                 .l()?,
         );
-        Ok(Some(key))
+        if self.entries {
+            Ok(Some(self.entry_part(key, self.dec.getkey_entry)?))
+        } else {
+            Ok(Some(key))
+        }
     }
     fn next_val(&self) -> Result<AutoLocal<'de, 'de>> {
-        Ok(self.dec.com.env.auto_local(
+        let val = self.dec.com.env.auto_local(
             self.dec
                 .com
                 .env
@@ -456,18 +832,41 @@ impl<'de> Map<'de> {
                     &[],
                 )?
                 .l()?,
+        );
+        if self.entries {
+            self.entry_part(val, self.dec.getvalue_entry)
+        } else {
+            Ok(val)
+        }
+    }
+    fn entry_part(
+        &self,
+        entry: AutoLocal<'de, 'de>,
+        method: JMethodID<'de>,
+    ) -> Result<AutoLocal<'de, 'de>> {
+        Ok(self.dec.com.env.auto_local(
+            self.dec
+                .com
+                .env
+                .call_method_unchecked(
+                    entry.as_obj(),
+                    method,
+                    JavaType::Object(String::new()),
+                    &[],
+                )?
+                .l()?,
         ))
     }
 }
 
-impl<'de, 'a> MapAccess<'de> for Map<'de> {
+impl<'de> MapAccess<'de> for Map<'de> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: DeserializeSeed<'de>,
     {
-        if let Some(key) = Map::next_key(&self)? {
+        if let Some(key) = Map::next_key(self)? {
             Ok(Some(
                 seed.deserialize(Deserializer::from_object(self.dec, key))?,
             ))
@@ -480,7 +879,7 @@ impl<'de, 'a> MapAccess<'de> for Map<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        Ok(seed.deserialize(Deserializer::from_object(self.dec, Map::next_val(&self)?))?)
+        seed.deserialize(Deserializer::from_object(self.dec, Map::next_val(self)?))
     }
 }
 
@@ -513,12 +912,13 @@ impl<'de> VariantAccess<'de> for Map<'de> {
         seed.deserialize(Deserializer::from_object(self.dec, Map::next_val(&self)?))
     }
 
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_seq(
+        de::Deserializer::deserialize_tuple(
             Deserializer::from_object(self.dec, Map::next_val(&self)?),
+            len,
             visitor,
         )
     }
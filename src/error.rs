@@ -1,4 +1,3 @@
-use std;
 use std::fmt::{self, Display};
 
 use serde::{de, ser};
@@ -9,6 +8,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     Message(String),
     JNI(jni::errors::Error),
+    /// A Java exception, described by its `toString`.
+    Exception(String),
     DeserializeAnyNotSupported,
     ExpectedBoolean,
     ExpectedBytes,
@@ -17,10 +18,20 @@ pub enum Error {
     ExpectedChar,
     ExpectedString,
     ExpectedKeyword,
+    ExpectedSymbol,
     ExpectedNull,
     ExpectedArray,
     ExpectedMap,
     ExpectedEnum,
+    ExpectedDirectBuffer,
+    ExpectedHandle,
+    ExpectedTaggedLiteral,
+    ExpectedTimestamp,
+    ExpectedDuration,
+    ExpectedUuid,
+    ExpectedRatio,
+    ExpectedDecimal,
+    ExpectedFn,
 }
 
 impl ser::Error for Error {
@@ -40,6 +51,7 @@ impl Display for Error {
         match *self {
             Error::Message(ref msg) => formatter.write_str(msg),
             Error::JNI(ref error) => write!(formatter, "JNI error: {}", error),
+            Error::Exception(ref desc) => write!(formatter, "Java exception: {}", desc),
             Error::DeserializeAnyNotSupported => {
                 formatter.write_str("deserialize_any not supported!")
             }
@@ -50,10 +62,20 @@ impl Display for Error {
             Error::ExpectedChar => formatter.write_str("ExpectedChar"),
             Error::ExpectedString => formatter.write_str("ExpectedString"),
             Error::ExpectedKeyword => formatter.write_str("ExpectedKeyword"),
+            Error::ExpectedSymbol => formatter.write_str("ExpectedSymbol"),
             Error::ExpectedNull => formatter.write_str("ExpectedNull"),
             Error::ExpectedArray => formatter.write_str("ExpectedArray"),
             Error::ExpectedMap => formatter.write_str("ExpectedMap"),
             Error::ExpectedEnum => formatter.write_str("ExpectedEnum"),
+            Error::ExpectedDirectBuffer => formatter.write_str("ExpectedDirectBuffer"),
+            Error::ExpectedHandle => formatter.write_str("ExpectedHandle"),
+            Error::ExpectedTaggedLiteral => formatter.write_str("ExpectedTaggedLiteral"),
+            Error::ExpectedTimestamp => formatter.write_str("ExpectedTimestamp"),
+            Error::ExpectedDuration => formatter.write_str("ExpectedDuration"),
+            Error::ExpectedUuid => formatter.write_str("ExpectedUuid"),
+            Error::ExpectedRatio => formatter.write_str("ExpectedRatio"),
+            Error::ExpectedDecimal => formatter.write_str("ExpectedDecimal"),
+            Error::ExpectedFn => formatter.write_str("ExpectedFn"),
         }
     }
 }
@@ -0,0 +1,351 @@
+//! Calling Clojure fns from Rust.
+
+use std::fmt;
+use std::iter;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use jni::objects::{GlobalRef, JClass, JObject};
+use jni::sys::{jlong, jobject};
+use jni::JNIEnv;
+use serde::de::value::SeqDeserializer;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::convert::{Common, Decoder, Encoder, EncoderOptions, Pinned};
+use crate::{from_object, marker, native, to_object, Error, Result};
+
+const RUST_FN: &str = "serde_clj/RustFn";
+
+/// A Clojure fn (or anything else implementing `clojure.lang.IFn`,
+/// like a var or a keyword) which can be called from Rust, e.g. a
+/// predicate passed to a native method:
+///
+/// ```no_run
+/// # use jni::{JNIEnv, objects::{JClass, JObject}, sys::jboolean};
+/// # use serde_clj::{from_object, Decoder, Encoder, Fn};
+/// #[no_mangle]
+/// pub extern "system" fn Java_Foo_matches(env: JNIEnv, _: JClass, pred: JObject) -> jboolean {
+///     let enc = Encoder::new(env.clone()).unwrap();
+///     let dec = Decoder::new(env).unwrap();
+///     let pred: Fn = from_object(&dec, pred).unwrap();
+///     pred.invoke::<bool, _>(&enc, &dec, &(42, "x")).unwrap() as jboolean
+/// }
+/// ```
+///
+/// Deserializing fails with `Error::ExpectedFn` for anything that
+/// isn't callable (use `Option<Fn>` to allow `nil`), and serializing
+/// gives back the same object. Like `Raw`, it holds a global
+/// reference and can't be used with other (de)serializers.
+#[derive(Clone)]
+pub struct Fn(GlobalRef);
+
+impl Fn {
+    /// Looks up a var, e.g. `"clojure.core/tap>"`, through
+    /// `clojure.java.api.Clojure.var`. The var's namespace must have
+    /// been loaded.
+    pub fn var(dec: &Decoder, name: &str) -> Result<Self> {
+        let env = &dec.com.env;
+        let class = env.auto_local(env.find_class("clojure/java/api/Clojure")?);
+        let name = env.auto_local(env.new_string(name)?);
+        let var = env
+            .call_static_method(
+                JClass::from(class.as_obj()),
+                "var",
+                "(Ljava/lang/Object;)Lclojure/lang/IFn;",
+                &[name.as_obj().into()],
+            )
+            .map_err(Error::from);
+        let var = env.auto_local(catch_exception(env, var)?.l()?);
+        Ok(Fn(env.new_global_ref(var.as_obj())?))
+    }
+
+    /// Calls the fn. `args` must serialize to a sequence of arguments,
+    /// usually a tuple: `&(1, "two")`, `&(x,)` for one argument, or
+    /// `&()` for none. An exception thrown by the fn is cleared and
+    /// returned as `Error::Exception`.
+    pub fn invoke<'a, T, A>(
+        &self,
+        enc: &'a Encoder<'a>,
+        dec: &'a Decoder<'a>,
+        args: &A,
+    ) -> Result<T>
+    where
+        T: Deserialize<'a>,
+        A: ?Sized + Serialize,
+    {
+        let args = to_object(enc, &args)?;
+        let out = dec.apply(JObject::from(self.as_obj().into_inner()), args);
+        from_object(dec, catch_exception(&dec.com.env, out)?)
+    }
+
+    pub fn as_obj(&self) -> JObject<'_> {
+        self.0.as_obj()
+    }
+
+    pub fn into_inner(self) -> GlobalRef {
+        self.0
+    }
+}
+
+impl fmt::Debug for Fn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Fn")
+            .field(&self.as_obj().into_inner())
+            .finish()
+    }
+}
+
+impl Serialize for Fn {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash(self.0.clone());
+        serializer.serialize_newtype_struct(marker::RAW, &())
+    }
+}
+
+impl<'de> Deserialize<'de> for Fn {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FnVisitor;
+
+        impl<'de> Visitor<'de> for FnVisitor {
+            type Value = Fn;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a Clojure fn")
+            }
+
+            // our Deserializer stashes the fn and visits unit
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+                marker::take::<GlobalRef>()
+                    .map(Fn)
+                    .ok_or_else(|| E::invalid_type(de::Unexpected::Unit, &self))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(marker::FN, FnVisitor)
+    }
+}
+
+/// A Rust closure which serializes to a Clojure fn (a
+/// `serde_clj.RustFn`), for handing out callbacks, predicates or
+/// comparators:
+///
+/// ```
+/// let add = serde_clj::RustFn::new(|(a, b): (i64, i64)| Ok(a + b));
+/// ```
+///
+/// The closure takes its arguments as one value deserialized from the
+/// argument list, usually a tuple (`()` for none, `Vec<T>` for any
+/// number), and its result is serialized back with the options of the
+/// `Encoder` which serialized the fn. An error or panic is thrown as a
+/// `RuntimeException`. The closure is shared by every fn serialized
+/// from the same `RustFn`, and dropped once they have all been garbage
+/// collected (this needs the `serde_clj.RustFn` and
+/// `serde_clj.NativeRelease` classes on the classpath). Other
+/// serializers see `()`.
+#[derive(Clone)]
+pub struct RustFn {
+    call: Arc<Call>,
+}
+
+/// A closure with its conversions.
+type Call = dyn for<'a> std::ops::Fn(&'a Encoder<'a>, &'a Decoder<'a>, JObject<'a>) -> Result<JObject<'a>>
+    + Send
+    + Sync;
+
+impl RustFn {
+    pub fn new<F, A, T>(f: F) -> Self
+    where
+        F: std::ops::Fn(A) -> Result<T> + Send + Sync + 'static,
+        A: DeserializeOwned,
+        T: Serialize,
+    {
+        Self {
+            call: Arc::new(move |enc, dec, args| {
+                // `(f)` passes no arg list at all
+                let args = if args.is_null() {
+                    A::deserialize(NoArgs)?
+                } else {
+                    from_object(dec, args)?
+                };
+                to_object(enc, &f(args)?)
+            }),
+        }
+    }
+}
+
+/// The arguments of a call without any, which are an empty seq (or
+/// unit, or `None`).
+struct NoArgs;
+
+impl<'de> Deserializer<'de> for NoArgs {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(SeqDeserializer::new(iter::empty::<()>()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_none()
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl fmt::Debug for RustFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RustFn")
+    }
+}
+
+impl Serialize for RustFn {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash::<native::Owned>(Box::new(self.call.clone()));
+        serializer.serialize_newtype_struct(marker::RUST_FN, &())
+    }
+}
+
+/// A closure with the options of the `Encoder` which serialized it,
+/// owned by a `serde_clj.RustFn`.
+struct Bound {
+    call: Arc<Call>,
+    options: EncoderOptions,
+    /// made on the first call and kept for the next, see `Pinned`
+    pinned: Mutex<Option<Pinned>>,
+}
+
+/// Wraps the closure in a new `serde_clj.RustFn`, which takes
+/// ownership.
+pub(crate) fn rust_fn_to_java<'a>(
+    enc: &'a Encoder<'a>,
+    owned: native::Owned,
+) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let call = owned
+        .downcast::<Arc<Call>>()
+        .map_err(|_| Error::Message("not a RustFn".into()))?;
+    let ptr = native::into_raw(Box::new(Bound {
+        call: *call,
+        options: enc.options(),
+        pinned: Mutex::new(None),
+    }));
+    let res = register(&enc.com).and_then(|()| {
+        let class = enc.com.class(RUST_FN)?;
+        Ok(env.new_object(JClass::from(class.as_obj()), "(J)V", &[ptr.into()])?)
+    });
+    let obj = match res {
+        Ok(obj) => obj,
+        Err(e) => {
+            drop(unsafe { native::from_raw(ptr) });
+            return Err(e);
+        }
+    };
+    native::attach_raw_cleaner(&enc.com, obj, ptr)?;
+    Ok(obj)
+}
+
+fn register(com: &Common) -> Result<()> {
+    native::register_natives(
+        com,
+        RUST_FN,
+        &[(
+            "call",
+            "(JLjava/lang/Object;)Ljava/lang/Object;",
+            call as *mut c_void,
+        )],
+    )
+}
+
+extern "system" fn call(env: JNIEnv, _this: JObject, ptr: jlong, args: JObject) -> jobject {
+    // `this` is reachable while its method runs, so the closure hasn't
+    // been released
+    let owned = unsafe { &*(ptr as *const native::Owned) };
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let f = owned
+            .downcast_ref::<Bound>()
+            .ok_or_else(|| Error::Message("not a RustFn".into()))?;
+        // room for the Encoder's and Decoder's class refs
+        env.push_local_frame(64)?;
+        let res = call_in_frame(&env, f, args);
+        let out = env.pop_local_frame(JObject::from(*res.as_ref().unwrap_or(&ptr::null_mut())))?;
+        res.map(|_| out.into_inner())
+    }));
+    throw_on_error(&env, res)
+}
+
+/// Returns the result of a native method, or throws a
+/// `RuntimeException` for an error or panic and returns `null`.
+pub(crate) fn throw_on_error(env: &JNIEnv, res: std::thread::Result<Result<jobject>>) -> jobject {
+    let msg = match res {
+        Ok(Ok(obj)) => return obj,
+        Ok(Err(e)) => e.to_string(),
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(msg) => format!("panic: {}", msg),
+            None => match panic.downcast_ref::<String>() {
+                Some(msg) => format!("panic: {}", msg),
+                None => "panic".into(),
+            },
+        },
+    };
+    // an exception may already be pending, e.g. from a JNI call
+    if !env.exception_check().unwrap_or(true) {
+        let _ = env.throw_new("java/lang/RuntimeException", msg);
+    }
+    JObject::null().into_inner()
+}
+
+fn call_in_frame(env: &JNIEnv, f: &Bound, args: JObject) -> Result<jobject> {
+    // a concurrent or reentrant call makes its own
+    let taken = f.pinned.lock().unwrap().take();
+    let mut pinned = match taken {
+        Some(pinned) => pinned,
+        None => Pinned::new(&f.options, env)?,
+    };
+    let (enc, dec) = pinned.bind(env);
+    let res = (f.call)(enc, dec, args).map(JObject::into_inner);
+    *f.pinned.lock().unwrap() = Some(pinned);
+    res
+}
+
+/// Turns the exception behind a failed JNI call into an
+/// `Error::Exception`, clearing it.
+pub(crate) fn catch_exception<T>(env: &JNIEnv, res: Result<T>) -> Result<T> {
+    match res {
+        Err(e) => match env.exception_check() {
+            Ok(true) => Err(take_exception(env)?),
+            _ => Err(e),
+        },
+        ok => ok,
+    }
+}
+
+fn take_exception(env: &JNIEnv) -> Result<Error> {
+    let ex = env.auto_local(JObject::from(env.exception_occurred()?));
+    env.exception_clear()?;
+    let desc = env
+        .call_method(ex.as_obj(), "toString", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let desc = env.auto_local(desc);
+    Ok(Error::Exception(
+        env.get_string(desc.as_obj().into())?.into(),
+    ))
+}
@@ -0,0 +1,171 @@
+//! Opaque handles to Rust values.
+
+use std::any;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use jni::objects::{JClass, JObject};
+use jni::sys::jlong;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::convert::{Decoder, Encoder};
+use crate::marker;
+use crate::native;
+use crate::Result;
+
+const HANDLE: &str = "serde_clj/Handle";
+
+/// A Rust value which serializes to an opaque `serde_clj.Handle`
+/// object instead of data, for state like parsers or connection
+/// pools. The value is shared, not copied, and is dropped once the
+/// last `Handle` (in Rust or Java) is gone; Java handles are released
+/// using a `java.lang.ref.Cleaner` (this needs the `serde_clj.Handle`
+/// and `serde_clj.NativeRelease` classes on the classpath).
+///
+/// Deserializing a handle gives back the same value. It fails if the
+/// handle holds a value of a different type.
+pub struct Handle<T> {
+    value: Arc<T>,
+}
+
+impl<T> Handle<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Arc::new(value),
+        }
+    }
+
+    pub fn arc(&self) -> &Arc<T> {
+        &self.value
+    }
+
+    pub fn into_arc(self) -> Arc<T> {
+        self.value
+    }
+}
+
+impl<T> From<Arc<T>> for Handle<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self { value }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.value).finish()
+    }
+}
+
+/// What `Handle` stashes for the `Serializer`.
+pub(crate) struct Export {
+    owner: native::Owned,
+    type_name: &'static str,
+}
+
+/// What the `Deserializer` stashes for `Handle`.
+pub(crate) struct Import {
+    ptr: jlong,
+    type_name: String,
+}
+
+impl<T: Send + Sync + 'static> Serialize for Handle<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash(Export {
+            owner: Box::new(self.value.clone()),
+            type_name: any::type_name::<T>(),
+        });
+        serializer.serialize_newtype_struct(marker::HANDLE, &())
+    }
+}
+
+impl<'de, T: Send + Sync + 'static> Deserialize<'de> for Handle<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HandleVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Send + Sync + 'static> Visitor<'de> for HandleVisitor<T> {
+            type Value = Handle<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a handle to {}", any::type_name::<T>())
+            }
+
+            // our Deserializer stashes the handle and visits unit
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+                let import = marker::take::<Import>()
+                    .ok_or_else(|| E::invalid_type(de::Unexpected::Unit, &self))?;
+                // The Deserializer holds a reference to the Java handle,
+                // so its value hasn't been released.
+                let owner = unsafe { &*(import.ptr as *const native::Owned) };
+                match owner.downcast_ref::<Arc<T>>() {
+                    Some(value) => Ok(Handle::from(value.clone())),
+                    None => Err(E::custom(format!(
+                        "expected a handle to {}, got a handle to {}",
+                        any::type_name::<T>(),
+                        import.type_name
+                    ))),
+                }
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(marker::HANDLE, HandleVisitor(PhantomData))
+    }
+}
+
+/// Wraps `export` in a new `serde_clj.Handle`, which takes ownership.
+pub(crate) fn to_java<'a>(enc: &'a Encoder<'a>, export: Export) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let type_name = env.auto_local(env.new_string(export.type_name)?);
+    let class = enc.com.class(HANDLE)?;
+    let ptr = native::into_raw(export.owner);
+    let res = env.new_object(
+        JClass::from(class.as_obj()),
+        "(JLjava/lang/String;)V",
+        &[ptr.into(), type_name.as_obj().into()],
+    );
+    let handle = match res {
+        Ok(handle) => handle,
+        Err(e) => {
+            drop(unsafe { native::from_raw(ptr) });
+            return Err(e.into());
+        }
+    };
+    native::attach_raw_cleaner(&enc.com, handle, ptr)?;
+    Ok(handle)
+}
+
+/// Reads the pointer and type name out of `obj`, if it is a handle.
+pub(crate) fn from_java(dec: &Decoder, obj: JObject) -> Result<Option<Import>> {
+    let env = &dec.com.env;
+    if !dec.com.is_instance_of_name(obj, HANDLE)? {
+        return Ok(None);
+    }
+    let ptr = env.get_field(obj, "ptr", "J")?.j()?;
+    let type_name = env.auto_local(env.get_field(obj, "type", "Ljava/lang/String;")?.l()?);
+    let type_name = env.get_string(type_name.as_obj().into())?.into();
+    Ok(Some(Import { ptr, type_name }))
+}
@@ -0,0 +1,130 @@
+//! Rust representations of Clojure keywords and symbols.
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::marker;
+
+/// Splits `"ns/name"` the way `clojure.lang.Symbol/intern` does.
+fn split(nsname: &str) -> (Option<String>, String) {
+    match nsname.find('/') {
+        Some(i) if nsname != "/" => (Some(nsname[..i].to_owned()), nsname[i + 1..].to_owned()),
+        _ => (None, nsname.to_owned()),
+    }
+}
+
+macro_rules! qualified {
+    ($(#[$doc:meta])* $type:ident, $marker:path, $prefix:expr, $expecting:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $type {
+            namespace: Option<String>,
+            name: String,
+        }
+
+        impl $type {
+            pub fn new<S: Into<String>>(name: S) -> Self {
+                Self {
+                    namespace: None,
+                    name: name.into(),
+                }
+            }
+
+            pub fn namespaced<N: Into<String>, S: Into<String>>(namespace: N, name: S) -> Self {
+                Self {
+                    namespace: Some(namespace.into()),
+                    name: name.into(),
+                }
+            }
+
+            /// Parses `"ns/name"` or `"name"`.
+            pub fn parse(nsname: &str) -> Self {
+                let (namespace, name) = split(nsname);
+                Self { namespace, name }
+            }
+
+            pub fn name(&self) -> &str {
+                &self.name
+            }
+
+            pub fn namespace(&self) -> Option<&str> {
+                self.namespace.as_deref()
+            }
+
+            /// `"ns/name"` or `"name"`, without any leading colon.
+            fn full_name(&self) -> String {
+                match &self.namespace {
+                    Some(ns) => format!("{}/{}", ns, self.name),
+                    None => self.name.clone(),
+                }
+            }
+        }
+
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}{}", $prefix, self.full_name())
+            }
+        }
+
+        impl Serialize for $type {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let full_name = self.full_name();
+                marker::stash(full_name.clone());
+                serializer.serialize_newtype_struct($marker, &full_name)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct NameVisitor;
+
+                impl<'de> Visitor<'de> for NameVisitor {
+                    type Value = $type;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str($expecting)
+                    }
+
+                    fn visit_str<E: de::Error>(self, v: &str) -> Result<$type, E> {
+                        Ok($type::parse(v))
+                    }
+
+                    fn visit_newtype_struct<D>(self, d: D) -> Result<$type, D::Error>
+                    where
+                        D: Deserializer<'de>,
+                    {
+                        d.deserialize_str(self)
+                    }
+                }
+
+                deserializer.deserialize_newtype_struct($marker, NameVisitor)
+            }
+        }
+    };
+}
+
+qualified!(
+    /// A Clojure keyword, e.g. `:status` or `:my.ns/status`. Serializes
+    /// to (and deserializes from) `clojure.lang.Keyword`.
+    Keyword,
+    marker::KEYWORD,
+    ":",
+    "a keyword"
+);
+
+qualified!(
+    /// A Clojure symbol, e.g. `inc` or `clojure.core/inc`. Serializes
+    /// to (and deserializes from) `clojure.lang.Symbol`.
+    Symbol,
+    marker::SYMBOL,
+    "",
+    "a symbol"
+);
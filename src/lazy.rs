@@ -0,0 +1,149 @@
+//! Lazy seqs of Rust iterators.
+
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+
+use jni::objects::{JClass, JObject};
+use jni::sys::{jint, jlong, jobject};
+use jni::JNIEnv;
+use serde::Serialize;
+
+use crate::convert::{ArrayList, Common, Encoder, EncoderOptions, Pinned};
+use crate::fns::throw_on_error;
+use crate::{native, to_object, Error, Result};
+
+const RUST_ITER: &str = "serde_clj/RustIter";
+
+/// The default number of items converted at a time, the same as the
+/// chunks of Clojure's own chunked seqs.
+const CHUNK_SIZE: usize = 32;
+
+/// An iterator with its conversions, owned by a `serde_clj.RustIter`.
+struct Iter {
+    options: EncoderOptions,
+    /// made for the first chunk and kept for the rest, see `Pinned`
+    pinned: Option<Pinned>,
+    pull: Box<Pull>,
+}
+
+/// Converts up to `n` more items into an `Object[]`, which is empty
+/// once the iterator is exhausted.
+type Pull = dyn for<'a> FnMut(&'a Encoder<'a>, usize) -> Result<JObject<'a>> + Send;
+
+/// Wraps a Rust iterator as a Clojure lazy seq, whose items are pulled
+/// and serialized (with the same options as `enc`) as the seq is
+/// realized:
+///
+/// ```no_run
+/// # use jni::{JNIEnv, objects::{JClass, JObject}, sys::jobject};
+/// # use serde_clj::Encoder;
+/// #[no_mangle]
+/// pub extern "system" fn Java_Foo_squares(env: JNIEnv, _: JClass) -> jobject {
+///     let enc = Encoder::new(env).unwrap();
+///     serde_clj::lazy_seq(&enc, (0..).map(|i: i64| i * i)).unwrap().into_inner()
+/// }
+/// ```
+///
+/// Items are converted 32 at a time (see `lazy_seq_chunked`), and the
+/// seq is chunked, so `reduce` and friends consume it a chunk at a
+/// time. An error or panic in the iterator or its conversion is thrown
+/// as a `RuntimeException` when the seq is realized. The iterator is
+/// dropped once the seq's unrealized rest has been garbage collected,
+/// or the seq has been fully realized. This needs Clojure and the
+/// `serde_clj.RustIter` and `serde_clj.NativeRelease` classes on the
+/// classpath.
+pub fn lazy_seq<'a, I>(enc: &'a Encoder<'a>, iter: I) -> Result<JObject<'a>>
+where
+    I: IntoIterator,
+    I::IntoIter: Send + 'static,
+    I::Item: Serialize,
+{
+    lazy_seq_chunked(enc, iter, CHUNK_SIZE)
+}
+
+/// Like `lazy_seq`, converting `chunk_size` items at a time. Smaller
+/// chunks realize less of the iterator ahead of time; larger ones make
+/// fewer calls into Rust.
+pub fn lazy_seq_chunked<'a, I>(
+    enc: &'a Encoder<'a>,
+    iter: I,
+    chunk_size: usize,
+) -> Result<JObject<'a>>
+where
+    I: IntoIterator,
+    I::IntoIter: Send + 'static,
+    I::Item: Serialize,
+{
+    if chunk_size == 0 || chunk_size > jint::MAX as usize {
+        return Err(Error::Message(format!("invalid chunk size {}", chunk_size)));
+    }
+    let mut iter = iter.into_iter();
+    let owned: native::Owned = Box::new(Mutex::new(Iter {
+        options: enc.options(),
+        pinned: None,
+        pull: Box::new(move |enc, n| {
+            let items = ArrayList::new(enc)?;
+            for item in iter.by_ref().take(n) {
+                items.add(to_object(enc, &item)?)?;
+            }
+            items.into_array()
+        }),
+    }));
+
+    let env = &enc.com.env;
+    register(&enc.com)?;
+    let class = enc.com.class(RUST_ITER)?;
+    let class = JClass::from(class.as_obj());
+    let ptr = native::into_raw(owned);
+    let res = env.new_object(class, "(JI)V", &[ptr.into(), (chunk_size as jint).into()]);
+    let obj = match res {
+        Ok(obj) => env.auto_local(obj),
+        Err(e) => {
+            drop(unsafe { native::from_raw(ptr) });
+            return Err(e.into());
+        }
+    };
+    native::attach_raw_cleaner(&enc.com, obj.as_obj(), ptr)?;
+    Ok(env
+        .call_method(obj.as_obj(), "seq", "()Lclojure/lang/ISeq;", &[])?
+        .l()?)
+}
+
+fn register(com: &Common) -> Result<()> {
+    native::register_natives(
+        com,
+        RUST_ITER,
+        &[("next", "(JI)[Ljava/lang/Object;", next as *mut c_void)],
+    )
+}
+
+extern "system" fn next(env: JNIEnv, _this: JObject, ptr: jlong, n: jint) -> jobject {
+    // `this` is reachable while its method runs, so the iterator hasn't
+    // been released
+    let owned = unsafe { &*(ptr as *const native::Owned) };
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let iter = owned
+            .downcast_ref::<Mutex<Iter>>()
+            .ok_or_else(|| Error::Message("not a RustIter".into()))?;
+        let mut iter = iter
+            .lock()
+            .map_err(|_| Error::Message("the iterator panicked earlier".into()))?;
+        // room for the Encoder's class refs
+        env.push_local_frame(64)?;
+        let res = next_in_frame(&env, &mut iter, n as usize);
+        let out = env.pop_local_frame(JObject::from(*res.as_ref().unwrap_or(&ptr::null_mut())))?;
+        res.map(|_| out.into_inner())
+    }));
+    throw_on_error(&env, res)
+}
+
+fn next_in_frame(env: &JNIEnv, iter: &mut Iter, n: usize) -> Result<jobject> {
+    let pinned = match &mut iter.pinned {
+        Some(pinned) => pinned,
+        pinned @ None => pinned.insert(Pinned::new(&iter.options, env)?),
+    };
+    let (enc, _) = pinned.bind(env);
+    Ok((iter.pull)(enc, n)?.into_inner())
+}
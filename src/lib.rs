@@ -10,6 +10,7 @@
 //! ## Example
 //!
 //! ```rust
+//! # use serde::Serialize;
 //! #[derive(Serialize)]
 //! struct MyStruct {
 //!     number: i32,
@@ -21,12 +22,57 @@
 //! {:number 3
 //!  :names ["foo" "bar"]}
 
+#[cfg(feature = "ndarray")]
+mod array;
+mod bean;
+mod buffer;
+mod coll;
+mod columns;
 mod convert;
 mod de;
 mod error;
+mod fns;
+mod handle;
+mod keyword;
+mod lazy;
+mod marker;
+mod meta;
+mod native;
+#[cfg(any(feature = "num-bigint", feature = "rust_decimal"))]
+mod num;
+mod raw;
+#[cfg(feature = "invocation")]
+mod runtime;
 mod ser;
+#[cfg(feature = "chrono")]
+mod time;
+#[cfg(feature = "uuid")]
+mod uuids;
 
-pub use convert::{Decoder, Encoder};
-pub use de::{from_object, Deserializer};
+#[cfg(feature = "ndarray")]
+pub use array::as_ndarray;
+pub use buffer::{BufferElement, DirectBuffer, PrimitiveArray};
+pub use coll::{as_list, as_set, as_sorted_map, as_sorted_set};
+pub use columns::{from_columns, to_columns};
+pub use convert::{Coercion, Decoder, Encoder};
+pub use de::{from_object, iter_from_object, Deserializer, SeqIter};
 pub use error::{Error, Result};
+pub use fns::{Fn, RustFn};
+pub use handle::Handle;
+pub use keyword::{Keyword, Symbol};
+pub use lazy::{lazy_seq, lazy_seq_chunked};
+pub use meta::WithMeta;
+#[cfg(feature = "num-bigint")]
+pub use num::as_bigint;
+#[cfg(feature = "rust_decimal")]
+pub use num::as_decimal;
+#[cfg(feature = "num-rational")]
+pub use num::as_ratio;
+pub use raw::Raw;
+#[cfg(feature = "invocation")]
+pub use runtime::Runtime;
 pub use ser::{to_object, Serializer};
+#[cfg(feature = "chrono")]
+pub use time::{as_duration, as_inst, as_instant, as_local_date};
+#[cfg(feature = "uuid")]
+pub use uuids::as_uuid;
@@ -0,0 +1,73 @@
+//! Private newtype markers.
+//!
+//! Some of our types need to hand the `Serializer` (or receive from
+//! the `Deserializer`) things that aren't part of the serde data
+//! model, like JVM objects or Rust-owned memory. They do this by
+//! stashing the value in a thread local and calling
+//! `serialize_newtype_struct`/`deserialize_newtype_struct` with one of
+//! the names below, which only our `Serializer` and `Deserializer`
+//! recognise. Other serializers just see an ordinary newtype.
+
+use std::any::Any;
+use std::cell::RefCell;
+
+use crate::{Error, Result};
+
+pub(crate) const DIRECT_BUFFER: &str = "$serde_clj::private::DirectBuffer";
+pub(crate) const PRIMITIVE_ARRAY: &str = "$serde_clj::private::PrimitiveArray";
+pub(crate) const KEYWORD: &str = "$serde_clj::private::Keyword";
+pub(crate) const SYMBOL: &str = "$serde_clj::private::Symbol";
+pub(crate) const HANDLE: &str = "$serde_clj::private::Handle";
+pub(crate) const RAW: &str = "$serde_clj::private::Raw";
+pub(crate) const FN: &str = "$serde_clj::private::Fn";
+pub(crate) const RUST_FN: &str = "$serde_clj::private::RustFn";
+pub(crate) const WITH_META: &str = "$serde_clj::private::WithMeta";
+pub(crate) const LIST: &str = "$serde_clj::private::List";
+pub(crate) const SET: &str = "$serde_clj::private::Set";
+pub(crate) const SORTED_SET: &str = "$serde_clj::private::SortedSet";
+pub(crate) const SORTED_MAP: &str = "$serde_clj::private::SortedMap";
+#[cfg(feature = "chrono")]
+pub(crate) const INSTANT: &str = "$serde_clj::private::Instant";
+#[cfg(feature = "chrono")]
+pub(crate) const INST: &str = "$serde_clj::private::Inst";
+#[cfg(feature = "chrono")]
+pub(crate) const LOCAL_DATE: &str = "$serde_clj::private::LocalDate";
+#[cfg(feature = "uuid")]
+pub(crate) const UUID: &str = "$serde_clj::private::Uuid";
+#[cfg(feature = "chrono")]
+pub(crate) const DURATION: &str = "$serde_clj::private::Duration";
+#[cfg(feature = "ndarray")]
+pub(crate) const NDARRAY: &str = "$serde_clj::private::NdArray";
+#[cfg(feature = "num-bigint")]
+pub(crate) const BIGINT: &str = "$serde_clj::private::BigInt";
+#[cfg(feature = "num-rational")]
+pub(crate) const RATIO: &str = "$serde_clj::private::Ratio";
+#[cfg(feature = "rust_decimal")]
+pub(crate) const DECIMAL: &str = "$serde_clj::private::Decimal";
+
+thread_local! {
+    static STASH: RefCell<Option<Box<dyn Any>>> = RefCell::new(None);
+}
+
+pub(crate) fn stash<T: 'static>(val: T) {
+    STASH.with(|s| *s.borrow_mut() = Some(Box::new(val)));
+}
+
+/// Drops whatever was stashed for a serializer other than ours, which
+/// never takes it, so that it is neither kept alive nor taken by us.
+pub(crate) fn clear() {
+    STASH.with(|s| s.borrow_mut().take());
+}
+
+pub(crate) fn take<T: 'static>() -> Option<T> {
+    STASH
+        .with(|s| s.borrow_mut().take())
+        .and_then(|val| val.downcast().ok())
+        .map(|val| *val)
+}
+
+/// Like `take`, for use by the `Serializer`/`Deserializer` when they
+/// see the marker `name`.
+pub(crate) fn take_for<T: 'static>(name: &str) -> Result<T> {
+    take().ok_or_else(|| Error::Message(format!("no value stashed for {}", name)))
+}
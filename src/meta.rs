@@ -0,0 +1,80 @@
+//! Clojure metadata.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::marker;
+
+/// A value with Clojure metadata.
+///
+/// Serializes to `value` with `meta` (which must serialize to a map
+/// or `nil`) attached using `IObj.withMeta`; `value` has to serialize
+/// to something which supports metadata, like a collection or a
+/// symbol. Deserializing reads both the value and its metadata, which
+/// is `nil` if there isn't any (so use an `Option` for `M` if it may
+/// be missing).
+///
+/// Other serializers see a newtype around the tuple `(value, meta)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WithMeta<T, M> {
+    pub value: T,
+    pub meta: M,
+}
+
+impl<T, M> WithMeta<T, M> {
+    pub fn new(value: T, meta: M) -> Self {
+        Self { value, meta }
+    }
+}
+
+impl<T: Serialize, M: Serialize> Serialize for WithMeta<T, M> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(marker::WITH_META, &(&self.value, &self.meta))
+    }
+}
+
+impl<'de, T: Deserialize<'de>, M: Deserialize<'de>> Deserialize<'de> for WithMeta<T, M> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MetaVisitor<T, M>(PhantomData<(T, M)>);
+
+        impl<'de, T: Deserialize<'de>, M: Deserialize<'de>> Visitor<'de> for MetaVisitor<T, M> {
+            type Value = WithMeta<T, M>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a value with metadata")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let value = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let meta = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(WithMeta { value, meta })
+            }
+
+            fn visit_newtype_struct<D>(self, d: D) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let (value, meta) = Deserialize::deserialize(d)?;
+                Ok(WithMeta { value, meta })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(marker::WITH_META, MetaVisitor(PhantomData))
+    }
+}
@@ -0,0 +1,137 @@
+use std::any::Any;
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+use jni::errors::{Error, ErrorKind};
+use jni::objects::{GlobalRef, JClass, JObject};
+use jni::sys::{jlong, jobject};
+use jni::{JNIEnv, NativeMethod};
+
+use crate::convert::Common;
+use crate::Result;
+
+/// A Rust value whose lifetime is managed by the JVM. It is dropped
+/// (on the JVM's cleaner thread) once the Java object it is attached
+/// to has been garbage collected.
+pub(crate) type Owned = Box<dyn Any + Send + Sync>;
+
+const NATIVE_RELEASE: &str = "serde_clj/NativeRelease";
+
+pub(crate) fn into_raw(owned: Owned) -> jlong {
+    Box::into_raw(Box::new(owned)) as jlong
+}
+
+/// # Safety
+///
+/// `ptr` must have been returned by `into_raw` and not yet released.
+pub(crate) unsafe fn from_raw(ptr: jlong) -> Owned {
+    *Box::from_raw(ptr as *mut Owned)
+}
+
+extern "system" fn release(_env: JNIEnv, _class: JClass, ptr: jlong) {
+    // only ever called once per pointer, by the Cleaner
+    drop(unsafe { from_raw(ptr) });
+}
+
+/// Arranges for `owned` to be dropped once `obj` becomes unreachable,
+/// using `java.lang.ref.Cleaner` (via `serde_clj.NativeRelease`).
+pub(crate) fn attach_cleaner(com: &Common, obj: JObject, owned: Owned) -> Result<()> {
+    attach_raw_cleaner(com, obj, into_raw(owned))
+}
+
+/// Like `attach_cleaner`, for a pointer returned by `into_raw`. If
+/// this fails, the pointer has already been released.
+pub(crate) fn attach_raw_cleaner(com: &Common, obj: JObject, ptr: jlong) -> Result<()> {
+    let res = register(com, obj, ptr);
+    if res.is_err() {
+        // the cleaner never saw it, so we still own it
+        drop(unsafe { from_raw(ptr) });
+    }
+    res
+}
+
+fn register(com: &Common, obj: JObject, ptr: jlong) -> Result<()> {
+    register_natives(
+        com,
+        NATIVE_RELEASE,
+        &[("release", "(J)V", release as *mut c_void)],
+    )?;
+    let class = com.class(NATIVE_RELEASE)?;
+    com.env.call_static_method(
+        JClass::from(class.as_obj()),
+        "register",
+        "(Ljava/lang/Object;J)V",
+        &[obj.into(), ptr.into()],
+    )?;
+    Ok(())
+}
+
+/// Registers the native methods (name, signature and function) of
+/// `class`, unless this has already been done for that class object.
+/// The JVM keeps them for as long as the class is loaded, but every
+/// class loader that loads `class` gets a class of its own (a reloaded
+/// REPL, an application server, or a `Runtime` next to its host), so
+/// the classes seen so far are kept as global references and compared
+/// by identity.
+pub(crate) fn register_natives(
+    com: &Common,
+    class: &'static str,
+    methods: &[(&str, &str, *mut c_void)],
+) -> Result<()> {
+    static REGISTERED: Mutex<Vec<GlobalRef>> = Mutex::new(Vec::new());
+    let global = com.class(class)?;
+    let mut registered = REGISTERED.lock().unwrap();
+    for seen in registered.iter() {
+        if com.env.is_same_object(seen.as_obj(), global.as_obj())? {
+            return Ok(());
+        }
+    }
+    let methods: Vec<_> = methods
+        .iter()
+        .map(|&(name, sig, fn_ptr)| NativeMethod {
+            name: name.into(),
+            sig: sig.into(),
+            fn_ptr,
+        })
+        .collect();
+    com.env
+        .register_native_methods(JClass::from(global.as_obj()), &methods)?;
+    registered.push(global);
+    Ok(())
+}
+
+/// Wraps `len` bytes at `data` in a direct `java.nio.ByteBuffer`
+/// without copying. The caller must keep the memory alive (and
+/// unmodified by Rust) for as long as the buffer is reachable.
+pub(crate) fn new_direct_byte_buffer<'a>(
+    env: &JNIEnv<'a>,
+    data: *const u8,
+    len: usize,
+) -> Result<JObject<'a>> {
+    let raw = env.get_native_interface();
+    let buf: jobject = unsafe {
+        let new = (**raw)
+            .NewDirectByteBuffer
+            .ok_or_else(|| Error::from(ErrorKind::JNIEnvMethodNotFound("NewDirectByteBuffer")))?;
+        new(raw, data as *mut c_void, len as jlong)
+    };
+    if buf.is_null() {
+        return Err(Error::from(ErrorKind::NullPtr("NewDirectByteBuffer result")).into());
+    }
+    Ok(buf.into())
+}
+
+/// Returns the start address and capacity (in elements) of a direct
+/// `java.nio.Buffer`, or `None` if `obj` isn't one.
+pub(crate) fn direct_buffer_region(env: &JNIEnv, obj: JObject) -> Option<(*const u8, usize)> {
+    let raw = env.get_native_interface();
+    unsafe {
+        let addr = (**raw).GetDirectBufferAddress?(raw, obj.into_inner());
+        let capacity = (**raw).GetDirectBufferCapacity?(raw, obj.into_inner());
+        if addr.is_null() || capacity < 0 {
+            None
+        } else {
+            Some((addr as *const u8, capacity as usize))
+        }
+    }
+}
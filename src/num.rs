@@ -0,0 +1,368 @@
+//! Modules for use with `#[serde(with = "...")]` which serialize
+//! arbitrary precision numbers as their Java counterparts (requires
+//! the `num-bigint`, `num-rational` or `rust_decimal` feature).
+//!
+//! | Rust                           | Clojure                   | Plain Java             |
+//! |--------------------------------|---------------------------|------------------------|
+//! | `num_bigint::BigInt`           | `clojure.lang.BigInt`     | `java.math.BigInteger` |
+//! | `num_rational::BigRational`    | `clojure.lang.Ratio`      | -                      |
+//! | `rust_decimal::Decimal`        | `java.math.BigDecimal`    | `java.math.BigDecimal` |
+//!
+//! Integers deserialize from any of `Long`, `Integer`, `Short`, `Byte`,
+//! `BigInt` and `BigInteger`, ratios also from integers and decimals
+//! also from integers. With `Coercion::Lenient`, any number or numeric
+//! string is accepted if it can be represented exactly. Other
+//! serializers see the number as a string.
+
+#[cfg(feature = "rust_decimal")]
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use jni::objects::{JClass, JObject};
+use serde::de::{self, Deserializer, Visitor};
+
+use crate::convert::{Coercion, Common, Decoder, Encoder};
+#[cfg(any(feature = "num-rational", feature = "rust_decimal"))]
+use crate::Error;
+use crate::{marker, Result};
+
+const BIG_INTEGER: &str = "java/math/BigInteger";
+#[cfg(feature = "rust_decimal")]
+const BIG_DECIMAL: &str = "java/math/BigDecimal";
+const CLJ_BIG_INT: &str = "clojure/lang/BigInt";
+#[cfg(feature = "num-rational")]
+const CLJ_RATIO: &str = "clojure/lang/Ratio";
+
+/// A `java.math.BigInteger` from its big-endian two's complement
+/// bytes.
+fn new_big_integer<'a>(com: &Common<'a>, bytes: &[u8]) -> Result<JObject<'a>> {
+    let env = &com.env;
+    let arr = env.auto_local(JObject::from(env.byte_array_from_slice(bytes)?));
+    let class = com.class(BIG_INTEGER)?;
+    Ok(env.new_object(
+        JClass::from(class.as_obj()),
+        "([B)V",
+        &[arr.as_obj().into()],
+    )?)
+}
+
+/// The big-endian two's complement bytes of a `BigInteger` or Clojure
+/// `BigInt`, or of a boxed integer.
+fn integer_bytes(dec: &Decoder, obj: JObject) -> Result<Option<Vec<u8>>> {
+    let env = &dec.com.env;
+    let big = if dec.com.is_instance_of_name(obj, BIG_INTEGER)? {
+        env.auto_local(env.new_local_ref::<JObject>(obj)?)
+    } else if dec.is_clojure() && dec.com.is_instance_of_name(obj, CLJ_BIG_INT)? {
+        env.auto_local(
+            env.call_method(obj, "toBigInteger", "()Ljava/math/BigInteger;", &[])?
+                .l()?,
+        )
+    } else if let Some(i) = decode_integer(dec, obj)? {
+        return Ok(Some(i.to_be_bytes().to_vec()));
+    } else {
+        return Ok(None);
+    };
+    let arr = env.auto_local(
+        env.call_method(big.as_obj(), "toByteArray", "()[B", &[])?
+            .l()?,
+    );
+    Ok(Some(env.convert_byte_array(arr.as_obj().into_inner())?))
+}
+
+/// A `Long`, `Integer`, `Short` or `Byte`.
+fn decode_integer(dec: &Decoder, obj: JObject) -> Result<Option<i64>> {
+    if let Some(i) = dec.decode_i64(obj)? {
+        Ok(Some(i))
+    } else if let Some(i) = dec.decode_i32(obj)? {
+        Ok(Some(i.into()))
+    } else if let Some(i) = dec.decode_i16(obj)? {
+        Ok(Some(i.into()))
+    } else {
+        Ok(dec.decode_i8(obj)?.map(i64::from))
+    }
+}
+
+/// The text of any number or string, for lenient decoding.
+fn lenient_text(dec: &Decoder, obj: JObject) -> Result<Option<String>> {
+    if dec.coercion == Coercion::Lenient {
+        dec.numeric_text(obj)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+pub(crate) fn bigint_to_java<'a>(
+    enc: &'a Encoder<'a>,
+    value: num_bigint::BigInt,
+) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let big = new_big_integer(&enc.com, &value.to_signed_bytes_be())?;
+    if !enc.is_clojure() {
+        return Ok(big);
+    }
+    let big = env.auto_local(big);
+    let class = enc.com.class(CLJ_BIG_INT)?;
+    Ok(env
+        .call_static_method(
+            JClass::from(class.as_obj()),
+            "fromBigInteger",
+            "(Ljava/math/BigInteger;)Lclojure/lang/BigInt;",
+            &[big.as_obj().into()],
+        )?
+        .l()?)
+}
+
+#[cfg(feature = "num-bigint")]
+pub(crate) fn bigint_from_java(dec: &Decoder, obj: JObject) -> Result<Option<num_bigint::BigInt>> {
+    if let Some(bytes) = integer_bytes(dec, obj)? {
+        return Ok(Some(num_bigint::BigInt::from_signed_bytes_be(&bytes)));
+    }
+    // e.g. "12" or 12.0
+    Ok(lenient_text(dec, obj)?.and_then(|s| {
+        let s = s.strip_suffix(".0").unwrap_or(&s);
+        s.strip_suffix('N').unwrap_or(s).parse().ok()
+    }))
+}
+
+#[cfg(feature = "num-rational")]
+pub(crate) fn ratio_to_java<'a>(
+    enc: &'a Encoder<'a>,
+    value: num_rational::BigRational,
+) -> Result<JObject<'a>> {
+    if !enc.is_clojure() {
+        return Err(Error::Message(
+            "ratios can only be serialized for Clojure".into(),
+        ));
+    }
+    let env = &enc.com.env;
+    let numer = env.auto_local(new_big_integer(
+        &enc.com,
+        &value.numer().to_signed_bytes_be(),
+    )?);
+    let denom = env.auto_local(new_big_integer(
+        &enc.com,
+        &value.denom().to_signed_bytes_be(),
+    )?);
+    // normalizes n/1 to an integer, like Clojure's `/`
+    let class = enc.com.class("clojure/lang/Numbers")?;
+    Ok(env
+        .call_static_method(
+            JClass::from(class.as_obj()),
+            "divide",
+            "(Ljava/math/BigInteger;Ljava/math/BigInteger;)Ljava/lang/Number;",
+            &[numer.as_obj().into(), denom.as_obj().into()],
+        )?
+        .l()?)
+}
+
+#[cfg(feature = "num-rational")]
+pub(crate) fn ratio_from_java(
+    dec: &Decoder,
+    obj: JObject,
+) -> Result<Option<num_rational::BigRational>> {
+    use num_bigint::BigInt;
+
+    if dec.is_clojure() && dec.com.is_instance_of_name(obj, CLJ_RATIO)? {
+        let env = &dec.com.env;
+        let part = |field: &str| -> Result<BigInt> {
+            let big = env.auto_local(env.get_field(obj, field, "Ljava/math/BigInteger;")?.l()?);
+            let arr = env.auto_local(
+                env.call_method(big.as_obj(), "toByteArray", "()[B", &[])?
+                    .l()?,
+            );
+            let bytes = env.convert_byte_array(arr.as_obj().into_inner())?;
+            Ok(BigInt::from_signed_bytes_be(&bytes))
+        };
+        let numer = part("numerator")?;
+        let denom = part("denominator")?;
+        if denom == BigInt::from(0) {
+            return Err(Error::Message("ratio with a zero denominator".into()));
+        }
+        return Ok(Some(num_rational::BigRational::new(numer, denom)));
+    }
+    if let Some(bytes) = integer_bytes(dec, obj)? {
+        return Ok(Some(BigInt::from_signed_bytes_be(&bytes).into()));
+    }
+    Ok(lenient_text(dec, obj)?.and_then(|s| s.parse().ok()))
+}
+
+#[cfg(feature = "rust_decimal")]
+pub(crate) fn decimal_to_java<'a>(
+    enc: &'a Encoder<'a>,
+    value: rust_decimal::Decimal,
+) -> Result<JObject<'a>> {
+    let env = &enc.com.env;
+    let unscaled = env.auto_local(new_big_integer(&enc.com, &value.mantissa().to_be_bytes())?);
+    let class = enc.com.class(BIG_DECIMAL)?;
+    Ok(env.new_object(
+        JClass::from(class.as_obj()),
+        "(Ljava/math/BigInteger;I)V",
+        &[unscaled.as_obj().into(), (value.scale() as i32).into()],
+    )?)
+}
+
+#[cfg(feature = "rust_decimal")]
+pub(crate) fn decimal_from_java(
+    dec: &Decoder,
+    obj: JObject,
+) -> Result<Option<rust_decimal::Decimal>> {
+    use rust_decimal::Decimal;
+
+    let out_of_range = || Error::Message("number out of range for Decimal".into());
+    let env = &dec.com.env;
+    let (bytes, scale) = if dec.com.is_instance_of_name(obj, BIG_DECIMAL)? {
+        let unscaled = env.auto_local(
+            env.call_method(obj, "unscaledValue", "()Ljava/math/BigInteger;", &[])?
+                .l()?,
+        );
+        let arr = env.auto_local(
+            env.call_method(unscaled.as_obj(), "toByteArray", "()[B", &[])?
+                .l()?,
+        );
+        let scale = env.call_method(obj, "scale", "()I", &[])?.i()?;
+        (env.convert_byte_array(arr.as_obj().into_inner())?, scale)
+    } else if let Some(bytes) = integer_bytes(dec, obj)? {
+        (bytes, 0)
+    } else {
+        return Ok(lenient_text(dec, obj)?.and_then(|s| {
+            let s = s.strip_suffix('M').unwrap_or(&s);
+            Decimal::from_str(s)
+                .or_else(|_| Decimal::from_scientific(s))
+                .ok()
+        }));
+    };
+    if bytes.len() > 16 {
+        return Err(out_of_range());
+    }
+    // sign extend to an i128
+    let fill = if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+        0xff
+    } else {
+        0
+    };
+    let mut buf = [fill; 16];
+    buf[16 - bytes.len()..].copy_from_slice(&bytes);
+    let mut mantissa = i128::from_be_bytes(buf);
+    let scale = if scale < 0 {
+        // e.g. 1E+3
+        mantissa = 10i128
+            .checked_pow(scale.unsigned_abs())
+            .and_then(|p| mantissa.checked_mul(p))
+            .ok_or_else(out_of_range)?;
+        0
+    } else {
+        u32::try_from(scale).map_err(|_| out_of_range())?
+    };
+    Decimal::try_from_i128_with_scale(mantissa, scale)
+        .map(Some)
+        .map_err(|_| out_of_range())
+}
+
+/// Receives a number from our `Deserializer` via the stash, or its
+/// string from any other.
+struct StashVisitor<T>(&'static str, PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for StashVisitor<T>
+where
+    T: 'static + FromStr,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<T, E> {
+        marker::take_for(self.0).map_err(de::Error::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<T, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+fn serialize_number<T, S>(
+    name: &'static str,
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    T: 'static + Clone + ToString,
+    S: serde::Serializer,
+{
+    marker::stash(value.clone());
+    serializer.serialize_newtype_struct(name, &value.to_string())
+}
+
+fn deserialize_number<'de, T, D>(
+    name: &'static str,
+    deserializer: D,
+) -> std::result::Result<T, D::Error>
+where
+    T: 'static + FromStr,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(name, StashVisitor(name, PhantomData))
+}
+
+macro_rules! number_module {
+    ($feature:literal, $name:ident, $ty:ty, $marker:ident, $doc:literal) => {
+        #[cfg(feature = $feature)]
+        #[doc = $doc]
+        pub mod $name {
+            use serde::{Deserializer, Serializer};
+
+            use crate::marker;
+
+            pub fn serialize<S>(value: &$ty, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                super::serialize_number(marker::$marker, value, serializer)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<$ty, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                super::deserialize_number(marker::$marker, deserializer)
+            }
+        }
+    };
+}
+
+number_module!(
+    "num-bigint",
+    as_bigint,
+    num_bigint::BigInt,
+    BIGINT,
+    "Serializes a `BigInt` as a `clojure.lang.BigInt` (`1N`), or for \
+     plain Java a `java.math.BigInteger`."
+);
+number_module!(
+    "num-rational",
+    as_ratio,
+    num_rational::BigRational,
+    RATIO,
+    "Serializes a `BigRational` as a `clojure.lang.Ratio` (`1/3`), or an \
+     integer if the denominator is 1."
+);
+number_module!(
+    "rust_decimal",
+    as_decimal,
+    rust_decimal::Decimal,
+    DECIMAL,
+    "Serializes a `Decimal` as a `java.math.BigDecimal` (`1.23M`) with \
+     the same scale."
+);
@@ -0,0 +1,83 @@
+//! Passing Java objects through unchanged.
+
+use std::fmt;
+
+use jni::objects::{GlobalRef, JObject};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::marker;
+
+/// A Java object which is carried through (de)serialization as is,
+/// e.g. a Clojure fn, an atom or a `java.io.File`.
+///
+/// Deserializing captures whatever object is at that position
+/// (including `nil`) in a global reference, and serializing puts the
+/// same object back. Other serializers see `()`, and it can't be
+/// deserialized by other deserializers.
+#[derive(Clone)]
+pub struct Raw(GlobalRef);
+
+impl Raw {
+    pub fn new(obj: GlobalRef) -> Self {
+        Raw(obj)
+    }
+
+    pub fn as_obj(&self) -> JObject<'_> {
+        self.0.as_obj()
+    }
+
+    pub fn into_inner(self) -> GlobalRef {
+        self.0
+    }
+}
+
+impl From<GlobalRef> for Raw {
+    fn from(obj: GlobalRef) -> Self {
+        Raw(obj)
+    }
+}
+
+impl fmt::Debug for Raw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Raw")
+            .field(&self.as_obj().into_inner())
+            .finish()
+    }
+}
+
+impl Serialize for Raw {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        marker::stash(self.0.clone());
+        serializer.serialize_newtype_struct(marker::RAW, &())
+    }
+}
+
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = Raw;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a Java object")
+            }
+
+            // our Deserializer stashes the object and visits unit
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+                marker::take::<GlobalRef>()
+                    .map(Raw)
+                    .ok_or_else(|| E::invalid_type(de::Unexpected::Unit, &self))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(marker::RAW, RawVisitor)
+    }
+}
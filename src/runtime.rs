@@ -0,0 +1,104 @@
+//! An embedded JVM for calling Clojure from a Rust program (requires
+//! the `invocation` feature).
+
+use std::path::Path;
+
+use jni::objects::JObject;
+use jni::{InitArgsBuilder, JNIEnv, JNIVersion, JavaVM};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::convert::{Decoder, Encoder};
+use crate::fns::catch_exception;
+use crate::{Error, Fn, Result, Symbol};
+
+/// A JVM started from Rust, for calling Clojure functions with
+/// serde values:
+///
+/// ```no_run
+/// # fn main() -> serde_clj::Result<()> {
+/// let runtime = serde_clj::Runtime::new(&["clojure.jar", "spec.alpha.jar", "core.specs.alpha.jar", "src"])?;
+/// runtime.require("clojure.string")?;
+/// let s: String = runtime.call("clojure.string/upper-case", &("hello",))?;
+/// assert_eq!(s, "HELLO");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Calls may be made from any thread, which is attached to the JVM
+/// (permanently) on its first call. Exceptions thrown by Clojure are
+/// cleared and returned as `Error::Exception`. Only one JVM can be
+/// started per process, and it is never shut down.
+pub struct Runtime {
+    vm: JavaVM,
+}
+
+impl Runtime {
+    /// Starts a JVM with the given classpath entries, which must
+    /// include Clojure (and its `spec.alpha` and `core.specs.alpha`
+    /// dependencies).
+    pub fn new<P: AsRef<Path>>(classpath: &[P]) -> Result<Self> {
+        let classpath = std::env::join_paths(classpath.iter().map(AsRef::as_ref))
+            .map_err(|e| Error::Message(format!("invalid classpath: {}", e)))?;
+        Self::with_options(&[format!("-Djava.class.path={}", classpath.to_string_lossy())])
+    }
+
+    /// Starts a JVM with the given options, e.g.
+    /// `-Djava.class.path=...` or `-Xmx2g`.
+    pub fn with_options<S: AsRef<str>>(options: &[S]) -> Result<Self> {
+        let mut args = InitArgsBuilder::new().version(JNIVersion::V8);
+        for option in options {
+            args = args.option(option.as_ref());
+        }
+        let args = args
+            .build()
+            .map_err(|e| Error::Message(format!("invalid JVM options: {}", e)))?;
+        Ok(Self {
+            vm: JavaVM::new(args)?,
+        })
+    }
+
+    /// The JVM, for using `jni` directly.
+    pub fn vm(&self) -> &JavaVM {
+        &self.vm
+    }
+
+    /// Loads the namespace `ns`, like `(require 'ns)`.
+    pub fn require(&self, ns: &str) -> Result<()> {
+        self.with_env(|env| {
+            let enc = Encoder::new(env.clone())?;
+            let dec = Decoder::new(env.clone())?;
+            let require = Fn::var(&dec, "clojure.core/require")?;
+            require.invoke::<(), _>(&enc, &dec, &(Symbol::new(ns),))
+        })
+    }
+
+    /// Calls the var named `var_name` (e.g. `"my.ns/f"`), whose namespace
+    /// must have been loaded with `require`. `args` must serialize to a
+    /// sequence of arguments, usually a tuple: `&(1, "two")`, `&(x,)`
+    /// for one argument, or `&()` for none.
+    pub fn call<T, A>(&self, var_name: &str, args: &A) -> Result<T>
+    where
+        T: DeserializeOwned,
+        A: ?Sized + Serialize,
+    {
+        self.with_env(|env| {
+            let enc = Encoder::new(env.clone())?;
+            let dec = Decoder::new(env.clone())?;
+            Fn::var(&dec, var_name)?.invoke(&enc, &dec, args)
+        })
+    }
+
+    /// Runs `f` with the current thread attached, in a local frame, and
+    /// turns a pending exception into an `Error`.
+    fn with_env<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&JNIEnv) -> Result<T>,
+    {
+        let env = self.vm.attach_current_thread_permanently()?;
+        env.push_local_frame(128)?;
+        let res = catch_exception(&env, f(&env));
+        env.pop_local_frame(JObject::null())?;
+        res
+    }
+}
@@ -1,14 +1,44 @@
-use jni::objects::JObject;
+use jni::objects::{GlobalRef, JObject};
 use serde::{self, ser, Serialize};
 
+#[cfg(feature = "ndarray")]
+use crate::array;
+use crate::buffer;
 use crate::convert::{ArrayList, Encoder};
 use crate::error::{Error, Result};
+use crate::fns;
+use crate::handle;
+use crate::marker;
+#[cfg(any(feature = "num-bigint", feature = "rust_decimal"))]
+use crate::num;
+#[cfg(feature = "chrono")]
+use crate::time;
+#[cfg(feature = "uuid")]
+use crate::uuids;
 
 pub struct Serializer<'a> {
     enc: &'a Encoder<'a>,
+    coll: Coll,
 }
 
-impl<'a> Serializer<'a> {}
+/// The Clojure collection to build for seqs and maps (see coll.rs).
+#[derive(Clone, Copy, PartialEq)]
+enum Coll {
+    /// vector or hash map
+    Default,
+    List,
+    Set,
+    SortedSet,
+    SortedMap,
+    /// a value and its metadata
+    Meta,
+}
+
+impl<'a> Serializer<'a> {
+    fn with_coll(self, coll: Coll) -> Self {
+        Self { coll, ..self }
+    }
+}
 
 // based on https://github.com/serde-rs/json/blob/master/src/value/ser.rs
 
@@ -16,7 +46,11 @@ pub fn to_object<'a, T>(enc: &'a Encoder<'a>, value: &T) -> Result<JObject<'a>>
 where
     T: Serialize,
 {
-    let serializer = Serializer { enc };
+    marker::clear();
+    let serializer = Serializer {
+        enc,
+        coll: Coll::Default,
+    };
     value.serialize(serializer)
 }
 
@@ -25,11 +59,18 @@ macro_rules! boxer {
         boxer!($func, $type as $type);
     };
     ($func:ident, $type:ty as $as:ty) => {
+        boxer!($func, $type as $as, canonical $as);
+    };
+    ($func:ident, $type:ty as $as:ty, canonical $canonical:ty) => {
         #[inline]
         fn $func(self, val: $type) -> Result<JObject<'a>> {
-            self.enc.to_boxed((val as $as).into())
+            if self.enc.canonical_numbers {
+                self.enc.to_boxed((val as $canonical).into())
+            } else {
+                self.enc.to_boxed((val as $as).into())
+            }
         }
-    }
+    };
 }
 
 pub fn variant_map<'a>(
@@ -40,7 +81,7 @@ pub fn variant_map<'a>(
     let res = ArrayList::new(enc)?;
     res.add(enc.get_keyword(variant)?)?;
     res.add(value)?;
-    Ok(res.to_hashmap()?.into())
+    res.into_hashmap()
 }
 
 impl<'a> serde::Serializer for Serializer<'a> {
@@ -57,17 +98,17 @@ impl<'a> serde::Serializer for Serializer<'a> {
 
     boxer!(serialize_bool, bool);
 
-    boxer!(serialize_i8, i8);
-    boxer!(serialize_i16, i16);
-    boxer!(serialize_i32, i32);
+    boxer!(serialize_i8, i8 as i8, canonical i64);
+    boxer!(serialize_i16, i16 as i16, canonical i64);
+    boxer!(serialize_i32, i32 as i32, canonical i64);
     boxer!(serialize_i64, i64);
 
-    boxer!(serialize_u8, u8 as i16);
-    boxer!(serialize_u16, u16 as i32);
+    boxer!(serialize_u8, u8 as i16, canonical i64);
+    boxer!(serialize_u16, u16 as i32, canonical i64);
     boxer!(serialize_u32, u32 as i64);
     boxer!(serialize_u64, u64 as i64); // TODO: BigInt here
 
-    boxer!(serialize_f32, f32);
+    boxer!(serialize_f32, f32 as f32, canonical f64);
     boxer!(serialize_f64, f64);
 
     #[inline]
@@ -100,27 +141,70 @@ impl<'a> serde::Serializer for Serializer<'a> {
     #[inline]
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<JObject<'a>> {
+        if let Some(class) = self.enc.java_enums.get(name) {
+            return self.enc.enum_constant(class, variant);
+        }
         // just a bare keyword
         self.enc.get_keyword(variant)
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<JObject<'a>>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<JObject<'a>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        match name {
+            marker::DIRECT_BUFFER => buffer::to_java(self.enc, marker::take_for(name)?),
+            marker::PRIMITIVE_ARRAY => buffer::array_to_java(self.enc, marker::take_for(name)?),
+            marker::HANDLE => handle::to_java(self.enc, marker::take_for(name)?),
+            marker::RUST_FN => fns::rust_fn_to_java(self.enc, marker::take_for(name)?),
+            marker::RAW => {
+                let obj = marker::take_for::<GlobalRef>(name)?;
+                let obj = JObject::from(obj.as_obj().into_inner());
+                Ok(self.enc.com.env.new_local_ref::<JObject>(obj)?)
+            }
+            #[cfg(feature = "chrono")]
+            marker::INSTANT => time::instant_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "chrono")]
+            marker::INST => time::inst_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "chrono")]
+            marker::LOCAL_DATE => time::local_date_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "chrono")]
+            marker::DURATION => time::duration_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "uuid")]
+            marker::UUID => uuids::to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "num-bigint")]
+            marker::BIGINT => num::bigint_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "num-rational")]
+            marker::RATIO => num::ratio_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "rust_decimal")]
+            marker::DECIMAL => num::decimal_to_java(self.enc, marker::take_for(name)?),
+            #[cfg(feature = "ndarray")]
+            marker::NDARRAY => {
+                if self.enc.shaped_arrays {
+                    marker::stash(array::AsShaped);
+                }
+                value.serialize(self)
+            }
+            marker::WITH_META => value.serialize(self.with_coll(Coll::Meta)),
+            marker::LIST => value.serialize(self.with_coll(Coll::List)),
+            marker::SET => value.serialize(self.with_coll(Coll::Set)),
+            marker::SORTED_SET => value.serialize(self.with_coll(Coll::SortedSet)),
+            marker::SORTED_MAP => value.serialize(self.with_coll(Coll::SortedMap)),
+            marker::KEYWORD => self.enc.get_keyword(&marker::take_for::<String>(name)?),
+            marker::SYMBOL => self.enc.get_symbol(&marker::take_for::<String>(name)?),
+            _ => match self.enc.tags.get(name) {
+                Some(tag) => self.enc.tagged(tag, to_object(self.enc, &value)?),
+                None => value.serialize(self),
+            },
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -128,7 +212,7 @@ impl<'a> serde::Serializer for Serializer<'a> {
         value: &T,
     ) -> Result<JObject<'a>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         variant_map(self.enc, variant, to_object(self.enc, &value)?)
     }
@@ -139,9 +223,9 @@ impl<'a> serde::Serializer for Serializer<'a> {
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<JObject<'a>>
+    fn serialize_some<T>(self, value: &T) -> Result<JObject<'a>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
@@ -150,6 +234,8 @@ impl<'a> serde::Serializer for Serializer<'a> {
         Ok(SerializeVec {
             enc: self.enc,
             vec: ArrayList::new(self.enc)?,
+            coll: self.coll,
+            type_name: None,
         })
     }
 
@@ -183,8 +269,10 @@ impl<'a> serde::Serializer for Serializer<'a> {
         self.serialize_seq(len.map(|l| l * 2))
     }
 
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        let mut map = self.serialize_map(Some(len))?;
+        map.type_name = Some(name);
+        Ok(map)
     }
 
     fn serialize_struct_variant(
@@ -200,6 +288,8 @@ impl<'a> serde::Serializer for Serializer<'a> {
             map: SerializeVec {
                 enc: self.enc,
                 vec: ArrayList::new(self.enc)?,
+                coll: Coll::Default,
+                type_name: None,
             },
         })
     }
@@ -208,6 +298,19 @@ impl<'a> serde::Serializer for Serializer<'a> {
 pub struct SerializeVec<'a> {
     enc: &'a Encoder<'a>,
     vec: ArrayList<'a>,
+    coll: Coll,
+    /// the struct name, for `Encoder::record` and `Encoder::type_meta`
+    type_name: Option<&'static str>,
+}
+
+impl<'a> SerializeVec<'a> {
+    /// The Java class of a struct registered with `Encoder::bean`.
+    fn bean_class(&self) -> Option<&'a str> {
+        let enc = self.enc;
+        self.type_name
+            .and_then(|name| enc.beans.get(name))
+            .map(String::as_str)
+    }
 }
 
 pub struct SerializeTupleVariant<'a> {
@@ -226,16 +329,22 @@ impl<'a> ser::SerializeSeq for SerializeVec<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let val = to_object(self.enc, &value)?;
         self.vec.add(val)
     }
 
     fn end(self) -> Result<JObject<'a>> {
-        self.vec.to_vector()
+        match self.coll {
+            Coll::List => self.vec.into_list(),
+            Coll::Set => self.vec.into_set(),
+            Coll::SortedSet => self.vec.into_sorted_set(),
+            Coll::Meta => self.vec.into_with_meta(),
+            _ => self.vec.into_vector(),
+        }
     }
 }
 
@@ -243,9 +352,9 @@ impl<'a> ser::SerializeTuple for SerializeVec<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -259,9 +368,9 @@ impl<'a> ser::SerializeTupleStruct for SerializeVec<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -275,16 +384,16 @@ impl<'a> ser::SerializeTupleVariant for SerializeTupleVariant<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.vec.add(to_object(self.enc, &value)?)?;
         Ok(())
     }
 
     fn end(self) -> Result<JObject<'a>> {
-        variant_map(self.enc, &self.name, self.vec.to_vector()?)
+        variant_map(self.enc, &self.name, self.vec.into_vector()?)
     }
 }
 
@@ -292,22 +401,25 @@ impl<'a> ser::SerializeMap for SerializeVec<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, key)
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, &value)
     }
 
     fn end(self) -> Result<JObject<'a>> {
-        self.vec.to_hashmap()
+        match self.coll {
+            Coll::SortedMap => self.vec.into_sorted_map(),
+            _ => self.vec.into_hashmap(),
+        }
     }
 }
 
@@ -315,16 +427,36 @@ impl<'a> ser::SerializeStruct for SerializeVec<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.vec.add(self.enc.get_keyword(key)?.into())?;
-        ser::SerializeMap::serialize_value(self, value)
+        let val = to_object(self.enc, &value)?;
+        if self.enc.omit_nil_fields && val.is_null() {
+            return Ok(());
+        }
+        if self.bean_class().is_some() {
+            self.vec.add(self.enc.com.env.new_string(key)?.into())?;
+        } else {
+            self.vec.add(self.enc.get_keyword(key)?)?;
+        }
+        self.vec.add(val)
     }
 
     fn end(self) -> Result<JObject<'a>> {
-        ser::SerializeMap::end(self)
+        let enc = self.enc;
+        let type_name = self.type_name;
+        if let Some(class) = self.bean_class() {
+            return self.vec.into_bean(class);
+        }
+        let mut map = ser::SerializeMap::end(self)?;
+        if let Some(class) = type_name.and_then(|name| enc.records.get(name)) {
+            map = enc.to_record(map, class)?;
+        }
+        match type_name {
+            Some(name) if enc.type_meta => enc.with_type_meta(map, name),
+            _ => Ok(map),
+        }
     }
 }
 
@@ -332,14 +464,14 @@ impl<'a> ser::SerializeStructVariant for SerializeStructVariant<'a> {
     type Ok = JObject<'a>;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeStruct::serialize_field(&mut self.map, key, value)
     }
 
     fn end(self) -> Result<JObject<'a>> {
-        variant_map(self.enc, &self.name, self.map.vec.to_hashmap()?)
+        variant_map(self.enc, &self.name, self.map.vec.into_hashmap()?)
     }
 }
@@ -0,0 +1,325 @@
+//! Modules for use with `#[serde(with = "...")]` which serialize
+//! timestamps and durations as `java.time` objects (requires the
+//! `chrono` feature).
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use chrono::{DateTime, NaiveDate, Utc};
+//! # use std::time::Duration;
+//! #[derive(Serialize, Deserialize)]
+//! struct Job {
+//!     #[serde(with = "serde_clj::as_instant")]
+//!     started: DateTime<Utc>,
+//!     #[serde(with = "serde_clj::as_local_date")]
+//!     due: NaiveDate,
+//!     #[serde(with = "serde_clj::as_duration")]
+//!     timeout: Duration,
+//! }
+//! ```
+//!
+//! Timestamps and dates deserialize from any of `java.time.Instant`,
+//! `java.util.Date` (what `#inst` reads as) and `java.time.LocalDate`
+//! (at midnight UTC). Other serializers see `(seconds, nanoseconds)`
+//! since the epoch.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+use jni::objects::{JClass, JObject};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use crate::convert::{Decoder, Encoder};
+use crate::{marker, Error, Result};
+
+const INSTANT: &str = "java/time/Instant";
+const DATE: &str = "java/util/Date";
+const LOCAL_DATE: &str = "java/time/LocalDate";
+const DURATION: &str = "java/time/Duration";
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A point in time, in seconds and nanoseconds since the epoch.
+#[derive(Clone, Copy)]
+pub(crate) struct Moment {
+    secs: i64,
+    nanos: u32,
+}
+
+/// A `std::time::Duration`'s parts.
+#[derive(Clone, Copy)]
+pub(crate) struct Span {
+    secs: u64,
+    nanos: u32,
+}
+
+pub(crate) fn instant_to_java<'a>(enc: &'a Encoder<'a>, m: Moment) -> Result<JObject<'a>> {
+    let class = enc.com.class(INSTANT)?;
+    Ok(enc
+        .com
+        .env
+        .call_static_method(
+            JClass::from(class.as_obj()),
+            "ofEpochSecond",
+            "(JJ)Ljava/time/Instant;",
+            &[m.secs.into(), i64::from(m.nanos).into()],
+        )?
+        .l()?)
+}
+
+pub(crate) fn inst_to_java<'a>(enc: &'a Encoder<'a>, m: Moment) -> Result<JObject<'a>> {
+    let millis = m
+        .secs
+        .checked_mul(1000)
+        .and_then(|ms| ms.checked_add(i64::from(m.nanos / 1_000_000)))
+        .ok_or_else(|| Error::Message("timestamp out of range for java.util.Date".into()))?;
+    let class = enc.com.class(DATE)?;
+    Ok(enc
+        .com
+        .env
+        .new_object(JClass::from(class.as_obj()), "(J)V", &[millis.into()])?)
+}
+
+pub(crate) fn local_date_to_java<'a>(enc: &'a Encoder<'a>, m: Moment) -> Result<JObject<'a>> {
+    let class = enc.com.class(LOCAL_DATE)?;
+    Ok(enc
+        .com
+        .env
+        .call_static_method(
+            JClass::from(class.as_obj()),
+            "ofEpochDay",
+            "(J)Ljava/time/LocalDate;",
+            &[m.secs.div_euclid(SECS_PER_DAY).into()],
+        )?
+        .l()?)
+}
+
+pub(crate) fn duration_to_java<'a>(enc: &'a Encoder<'a>, s: Span) -> Result<JObject<'a>> {
+    let secs = i64::try_from(s.secs)
+        .map_err(|_| Error::Message("duration out of range for java.time.Duration".into()))?;
+    let class = enc.com.class(DURATION)?;
+    Ok(enc
+        .com
+        .env
+        .call_static_method(
+            JClass::from(class.as_obj()),
+            "ofSeconds",
+            "(JJ)Ljava/time/Duration;",
+            &[secs.into(), i64::from(s.nanos).into()],
+        )?
+        .l()?)
+}
+
+/// Reads an `Instant`, `Date` or `LocalDate`.
+pub(crate) fn moment_from_java(dec: &Decoder, obj: JObject) -> Result<Option<Moment>> {
+    let env = &dec.com.env;
+    if dec.com.is_instance_of_name(obj, INSTANT)? {
+        let secs = env.call_method(obj, "getEpochSecond", "()J", &[])?.j()?;
+        let nanos = env.call_method(obj, "getNano", "()I", &[])?.i()?;
+        Ok(Some(Moment {
+            secs,
+            nanos: nanos as u32,
+        }))
+    } else if dec.com.is_instance_of_name(obj, DATE)? {
+        let millis = env.call_method(obj, "getTime", "()J", &[])?.j()?;
+        Ok(Some(Moment {
+            secs: millis.div_euclid(1000),
+            nanos: millis.rem_euclid(1000) as u32 * 1_000_000,
+        }))
+    } else if dec.com.is_instance_of_name(obj, LOCAL_DATE)? {
+        let days = env.call_method(obj, "toEpochDay", "()J", &[])?.j()?;
+        Ok(Some(Moment {
+            secs: days.saturating_mul(SECS_PER_DAY),
+            nanos: 0,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reads a `java.time.Duration`.
+pub(crate) fn span_from_java(dec: &Decoder, obj: JObject) -> Result<Option<Span>> {
+    if !dec.com.is_instance_of_name(obj, DURATION)? {
+        return Ok(None);
+    }
+    let env = &dec.com.env;
+    let secs = env.call_method(obj, "getSeconds", "()J", &[])?.j()?;
+    let nanos = env.call_method(obj, "getNano", "()I", &[])?.i()?;
+    let secs = u64::try_from(secs).map_err(|_| Error::Message("negative duration".into()))?;
+    Ok(Some(Span {
+        secs,
+        nanos: nanos as u32,
+    }))
+}
+
+/// Receives a `Moment` or `Span` from our `Deserializer` via the
+/// stash, or `(secs, nanos)` from any other.
+struct StashVisitor<T, F>(&'static str, PhantomData<(T, F)>);
+
+impl<'de, T, F> Visitor<'de> for StashVisitor<T, F>
+where
+    T: 'static + From<F>,
+    F: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a timestamp or duration")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<T, E> {
+        marker::take_for(self.0).map_err(de::Error::custom)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        F::deserialize(deserializer).map(T::from)
+    }
+}
+
+impl From<(i64, u32)> for Moment {
+    fn from((secs, nanos): (i64, u32)) -> Self {
+        Moment { secs, nanos }
+    }
+}
+
+impl From<(u64, u32)> for Span {
+    fn from((secs, nanos): (u64, u32)) -> Self {
+        Span { secs, nanos }
+    }
+}
+
+fn serialize_moment<S: serde::Serializer>(
+    name: &'static str,
+    m: Moment,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    marker::stash(m);
+    serializer.serialize_newtype_struct(name, &(m.secs, m.nanos))
+}
+
+fn deserialize_moment<'de, D: Deserializer<'de>>(
+    name: &'static str,
+    deserializer: D,
+) -> std::result::Result<Moment, D::Error> {
+    deserializer
+        .deserialize_newtype_struct(name, StashVisitor::<Moment, (i64, u32)>(name, PhantomData))
+}
+
+fn to_utc<E: de::Error>(m: Moment) -> std::result::Result<chrono::DateTime<chrono::Utc>, E> {
+    chrono::DateTime::from_timestamp(m.secs, m.nanos)
+        .ok_or_else(|| E::custom("timestamp out of range"))
+}
+
+macro_rules! timestamp_module {
+    ($name:ident, $marker:ident, $doc:literal) => {
+        #[doc = $doc]
+        pub mod $name {
+            use chrono::{DateTime, Utc};
+            use serde::{Deserializer, Serializer};
+
+            use crate::marker;
+
+            pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let m = super::Moment {
+                    secs: value.timestamp(),
+                    nanos: value.timestamp_subsec_nanos(),
+                };
+                super::serialize_moment(marker::$marker, m, serializer)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                super::to_utc(super::deserialize_moment(marker::$marker, deserializer)?)
+            }
+        }
+    };
+}
+
+timestamp_module!(
+    as_instant,
+    INSTANT,
+    "Serializes a `DateTime<Utc>` as a `java.time.Instant`."
+);
+timestamp_module!(
+    as_inst,
+    INST,
+    "Serializes a `DateTime<Utc>` as a `java.util.Date`, which prints \
+     as `#inst` (with millisecond precision)."
+);
+
+/// Serializes a `NaiveDate` as a `java.time.LocalDate`.
+pub mod as_local_date {
+    use chrono::NaiveDate;
+    use serde::{Deserializer, Serializer};
+
+    use crate::marker;
+
+    pub fn serialize<S>(value: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let secs = value
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is valid")
+            .and_utc()
+            .timestamp();
+        super::serialize_moment(
+            marker::LOCAL_DATE,
+            super::Moment { secs, nanos: 0 },
+            serializer,
+        )
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m = super::deserialize_moment(marker::LOCAL_DATE, deserializer)?;
+        Ok(super::to_utc(m)?.date_naive())
+    }
+}
+
+/// Serializes a `std::time::Duration` as a `java.time.Duration`.
+pub mod as_duration {
+    use std::marker::PhantomData;
+    use std::time::Duration;
+
+    use serde::{Deserializer, Serializer};
+
+    use super::{Span, StashVisitor};
+    use crate::marker;
+
+    pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let span = Span {
+            secs: value.as_secs(),
+            nanos: value.subsec_nanos(),
+        };
+        marker::stash(span);
+        serializer.serialize_newtype_struct(marker::DURATION, &(span.secs, span.nanos))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let span = deserializer.deserialize_newtype_struct(
+            marker::DURATION,
+            StashVisitor::<Span, (u64, u32)>(marker::DURATION, PhantomData),
+        )?;
+        if span.nanos >= 1_000_000_000 {
+            return Err(serde::de::Error::custom("nanoseconds out of range"));
+        }
+        Ok(Duration::new(span.secs, span.nanos))
+    }
+}
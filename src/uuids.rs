@@ -0,0 +1,178 @@
+//! `uuid::Uuid` as `java.util.UUID` (requires the `uuid` feature).
+
+use jni::objects::{JClass, JObject};
+use uuid::Uuid;
+
+use crate::convert::{Decoder, Encoder};
+use crate::Result;
+
+const UUID: &str = "java/util/UUID";
+
+pub(crate) fn to_java<'a>(enc: &'a Encoder<'a>, id: Uuid) -> Result<JObject<'a>> {
+    let (hi, lo) = id.as_u64_pair();
+    let class = enc.com.class(UUID)?;
+    Ok(enc.com.env.new_object(
+        JClass::from(class.as_obj()),
+        "(JJ)V",
+        &[(hi as i64).into(), (lo as i64).into()],
+    )?)
+}
+
+pub(crate) fn from_java(dec: &Decoder, obj: JObject) -> Result<Option<Uuid>> {
+    if !dec.com.is_instance_of_name(obj, UUID)? {
+        return Ok(None);
+    }
+    let env = &dec.com.env;
+    let hi = env
+        .call_method(obj, "getMostSignificantBits", "()J", &[])?
+        .j()?;
+    let lo = env
+        .call_method(obj, "getLeastSignificantBits", "()J", &[])?
+        .j()?;
+    Ok(Some(Uuid::from_u64_pair(hi as u64, lo as u64)))
+}
+
+/// For use with `#[serde(with = "serde_clj::as_uuid")]` on a `Uuid`,
+/// or an `Option`, `Vec`, `HashMap` or `BTreeMap` (as keys) of them,
+/// which serializes them as `java.util.UUID`s (Clojure's `#uuid`).
+/// Deserialization accepts a `java.util.UUID` or a string. Other
+/// serializers see the `Uuid` as usual.
+pub mod as_uuid {
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt;
+    use std::hash::BuildHasher;
+
+    use serde::de::{self, Deserialize, Deserializer, Visitor};
+    use serde::ser::{Serialize, Serializer};
+    use uuid::Uuid;
+
+    use crate::marker;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: UuidForm,
+        S: Serializer,
+    {
+        value.serialize_uuids(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: UuidForm,
+        D: Deserializer<'de>,
+    {
+        T::deserialize_uuids(deserializer)
+    }
+
+    /// The types `as_uuid` can be used with.
+    pub trait UuidForm: Sized {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+    }
+
+    /// Borrows a value to serialize it with `UuidForm`.
+    struct Ser<'a, T>(&'a T);
+
+    impl<'a, T: UuidForm> Serialize for Ser<'a, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize_uuids(serializer)
+        }
+    }
+
+    /// Deserializes a value with `UuidForm`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct De<T>(T);
+
+    impl<'de, T: UuidForm> Deserialize<'de> for De<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            T::deserialize_uuids(deserializer).map(De)
+        }
+    }
+
+    impl UuidForm for Uuid {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            marker::stash(*self);
+            serializer.serialize_newtype_struct(marker::UUID, self)
+        }
+
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct UuidVisitor;
+
+            impl<'de> Visitor<'de> for UuidVisitor {
+                type Value = Uuid;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a UUID")
+                }
+
+                // our Deserializer stashes a java.util.UUID and visits
+                // unit
+                fn visit_unit<E: de::Error>(self) -> Result<Uuid, E> {
+                    marker::take_for(marker::UUID).map_err(E::custom)
+                }
+
+                fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Uuid, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    Uuid::deserialize(deserializer)
+                }
+            }
+
+            deserializer.deserialize_newtype_struct(marker::UUID, UuidVisitor)
+        }
+    }
+
+    impl<T: UuidForm> UuidForm for Option<T> {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Some(value) => serializer.serialize_some(&Ser(value)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Option::<De<T>>::deserialize(deserializer)?.map(|De(value)| value))
+        }
+    }
+
+    impl<T: UuidForm> UuidForm for Vec<T> {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter().map(Ser))
+        }
+
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let values = Vec::<De<T>>::deserialize(deserializer)?;
+            Ok(values.into_iter().map(|De(value)| value).collect())
+        }
+    }
+
+    impl<V, H> UuidForm for HashMap<Uuid, V, H>
+    where
+        V: Serialize + for<'de> Deserialize<'de>,
+        H: BuildHasher + Default,
+    {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter().map(|(k, v)| (Ser(k), v)))
+        }
+
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = HashMap::<De<Uuid>, V>::deserialize(deserializer)?;
+            Ok(entries.into_iter().map(|(De(k), v)| (k, v)).collect())
+        }
+    }
+
+    impl<V> UuidForm for BTreeMap<Uuid, V>
+    where
+        V: Serialize + for<'de> Deserialize<'de>,
+    {
+        fn serialize_uuids<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter().map(|(k, v)| (Ser(k), v)))
+        }
+
+        fn deserialize_uuids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<De<Uuid>, V>::deserialize(deserializer)?;
+            Ok(entries.into_iter().map(|(De(k), v)| (k, v)).collect())
+        }
+    }
+}
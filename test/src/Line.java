@@ -0,0 +1 @@
+public record Line(int fromX, int toX, String label) {}
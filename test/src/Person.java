@@ -0,0 +1,16 @@
+public class Person {
+    private String firstName;
+    private int age;
+    private boolean active;
+    private byte level;
+
+    public String getFirstName() { return firstName; }
+    public void setFirstName(String firstName) { this.firstName = firstName; }
+    public int getAge() { return age; }
+    public void setAge(int age) { this.age = age; }
+    public void setAge(String age) { this.age = Integer.parseInt(age); }
+    public boolean isActive() { return active; }
+    public void setActive(boolean active) { this.active = active; }
+    public byte getLevel() { return level; }
+    public void setLevel(byte level) { this.level = level; }
+}
@@ -1,7 +1,46 @@
 public class Test {
     public static native Object ser(int n);
+    public static native Object serCanonical(int n);
+    public static native Object serOmitNil(int n);
     public static native void de(Object obj);
+    public static native void deStrict(Object obj);
+    public static native Object deLenient(Object obj);
+    public static native Object deError(Object obj, boolean strict);
+    public static native Object deNil(Object obj);
     public static native Object roundtrip(Object obj);
+    public static native Object roundtripNames(Object obj);
+    public static native Object roundtripColls(Object obj);
+    public static native Object roundtripRaw(Object obj);
+    public static native Object roundtripMeta(Object obj);
+    public static native Object serTypeMeta();
+    public static native Object roundtripTagged(Object obj);
+    public static native Object roundtripRecord(Object obj);
+    public static native Object roundtripBean(Object obj);
+    public static native Object roundtripLine(Object obj);
+    public static native Object canonicalBeans(Object person, Object line);
+    public static native Object beanErrors(Object obj, String beanClass);
+    public static native Object roundtripEnum(Object obj);
+    public static native Object enumError();
+    public static native Object roundtripTimes(Object obj);
+    public static native Object roundtripUuids(Object obj);
+    public static native Object roundtripNums(Object obj);
+    public static native Object roundtripMatrices(Object obj, boolean shaped);
+    public static native Object matricesAfterJson(Object obj);
+    public static native Object toColumns(Object obj);
+    public static native Object fromColumns(Object obj);
+    public static native Object callFn(Object obj);
+    public static native Object callVar(Object name, Object args);
+    public static native Object rustFns();
+    public static native Object lazySquares(int chunkSize);
+    public static native Object sumLongs(Object xs);
+    public static native Object serJava(int n);
+    public static native Object roundtripJava(Object obj);
+    public static native Object buffer(int n);
+    public static native int sumBytes(Object obj);
+    public static native int sumByteVec(Object obj);
+    public static native Object counter(int start);
+    public static native int counterStart(Object obj);
+    public static native boolean isBuffer(Object obj);
 
     static {
         System.loadLibrary("testlib");
@@ -1,10 +1,20 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use jni::objects::{JClass, JObject};
-use jni::sys::{jint, jobject};
+use jni::sys::{jboolean, jint, jobject};
 use jni::JNIEnv;
+use ndarray::{Array2, ArrayD};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use serde_clj::{from_object, to_object, Decoder, Encoder};
-use std::collections::HashMap;
-use std::iter::repeat;
+use serde_clj::{
+    from_columns, from_object, to_columns, to_object, Coercion, Decoder, DirectBuffer, Encoder, Fn,
+    Handle, Keyword, Raw, RustFn, Symbol, WithMeta,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::iter::repeat_n;
+use std::time::Duration;
+use uuid::Uuid;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 enum Vars {
@@ -23,6 +33,7 @@ struct Test {
     // This gets encoded as a 1 char string, since java's char type
     // does not support all utf8 characters
     a_char: char,
+    a_float: f32,
     tuple: (i32, String),
     opt_tuple: (i32, Option<String>),
     enumerate: Vec<Vars>,
@@ -30,16 +41,15 @@ struct Test {
     bytes: Vec<u8>,
 }
 
-#[no_mangle]
-pub extern "system" fn Java_Test_ser(env: JNIEnv, _: JClass, n: jint) -> jobject {
-    let enc = Encoder::new(env).unwrap();
+fn make_test() -> Test {
     let mut map: HashMap<i32, String> = HashMap::new();
     map.insert(7, "test".into());
-    let test = Test {
+    Test {
         value: vec![1, 2, 3],
         another_field: None,
         a_string: "test".into(),
         a_char: '𩸽',
+        a_float: 1.5,
         tuple: (4, "hey".into()),
         opt_tuple: (2, None),
         enumerate: vec![
@@ -54,8 +64,184 @@ pub extern "system" fn Java_Test_ser(env: JNIEnv, _: JClass, n: jint) -> jobject
             },
         ],
         bytes: vec![0, 1, 2],
-    };
-    let vec = repeat(test).take(n as usize).collect::<Vec<_>>();
+    }
+}
+
+fn make_tests(n: jint) -> Vec<Test> {
+    repeat_n(make_test(), n as usize).collect()
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(default)]
+struct Opts {
+    names: Vec<String>,
+    counts: HashMap<String, i32>,
+    label: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Names {
+    status: Keyword,
+    f: Symbol,
+    index: HashMap<Keyword, i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Colls {
+    #[serde(with = "serde_clj::as_list")]
+    list: Vec<i32>,
+    #[serde(with = "serde_clj::as_set")]
+    set: HashSet<String>,
+    #[serde(with = "serde_clj::as_sorted_set")]
+    sorted_set: BTreeSet<i64>,
+    #[serde(with = "serde_clj::as_sorted_map")]
+    sorted_map: BTreeMap<String, i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Owners {
+    #[serde(with = "serde_clj::as_uuid")]
+    id: Uuid,
+    #[serde(with = "serde_clj::as_uuid")]
+    owners: HashMap<Uuid, String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Amounts {
+    #[serde(with = "serde_clj::as_bigint")]
+    total: BigInt,
+    #[serde(with = "serde_clj::as_ratio")]
+    share: BigRational,
+    #[serde(with = "serde_clj::as_decimal")]
+    price: Decimal,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Matrices {
+    #[serde(with = "serde_clj::as_ndarray")]
+    weights: Array2<f64>,
+    #[serde(with = "serde_clj::as_ndarray")]
+    counts: ArrayD<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Trade {
+    id: i64,
+    symbol: String,
+    price: f64,
+    qty: Option<i32>,
+    venues: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Call {
+    f: Fn,
+    xs: Vec<i64>,
+}
+
+#[derive(Serialize)]
+struct RustFns {
+    add: RustFn,
+    compare: RustFn,
+    sum: RustFn,
+    half: RustFn,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Times {
+    #[serde(with = "serde_clj::as_inst")]
+    created: DateTime<Utc>,
+    #[serde(with = "serde_clj::as_instant")]
+    updated: DateTime<Utc>,
+    #[serde(with = "serde_clj::as_local_date")]
+    due: NaiveDate,
+    #[serde(with = "serde_clj::as_duration")]
+    timeout: Duration,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum TimeUnit {
+    Seconds,
+    Minutes,
+    // not a java.util.concurrent.TimeUnit
+    Fortnights,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Person {
+    first_name: String,
+    age: i32,
+    active: bool,
+    #[serde(default)]
+    level: u8,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Line {
+    from_x: i32,
+    to_x: i32,
+    label: String,
+}
+
+/// Like `Person`, with a field neither `Person` nor `Line` has.
+#[derive(Deserialize, Serialize, Debug)]
+struct Badge {
+    first_name: String,
+    nickname: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Callback {
+    name: String,
+    f: Raw,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Source {
+    line: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename = "testlib::Sink")]
+struct Sink {
+    line: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct UserId(i64);
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug)]
+struct Counter {
+    start: i32,
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_ser(env: JNIEnv, _: JClass, n: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap();
+    let vec = make_tests(n);
+    let output = to_object(&enc, &vec).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_serCanonical(env: JNIEnv, _: JClass, n: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap().canonical_numbers(true);
+    let vec = make_tests(n);
+    let output = to_object(&enc, &vec).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_serOmitNil(env: JNIEnv, _: JClass, n: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap().omit_nil_fields(true);
+    let vec = make_tests(n);
     let output = to_object(&enc, &vec).expect("serialisation failed!");
     output.into_inner()
 }
@@ -67,6 +253,126 @@ pub extern "system" fn Java_Test_de(env: JNIEnv, _: JClass, obj: JObject) {
     println!("{:?}", out);
 }
 
+#[no_mangle]
+pub extern "system" fn Java_Test_deStrict(env: JNIEnv, _: JClass, obj: JObject) {
+    let dec = Decoder::new(env).unwrap().coercion(Coercion::Strict);
+    let _: Vec<Test> = from_object(&dec, obj).expect("deserialisation failed");
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_deLenient(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone())
+        .unwrap()
+        .coercion(Coercion::Lenient);
+    let out: (u8, i64, f32, bool) = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+/// The error from decoding `obj` like `deStrict` or `deLenient`, or
+/// nil if it decodes.
+#[no_mangle]
+pub extern "system" fn Java_Test_deError(
+    env: JNIEnv,
+    _: JClass,
+    obj: JObject,
+    strict: jboolean,
+) -> jobject {
+    let coercion = if strict != 0 {
+        Coercion::Strict
+    } else {
+        Coercion::Lenient
+    };
+    let dec = Decoder::new(env.clone()).unwrap().coercion(coercion);
+    let err = if strict != 0 {
+        from_object::<Vec<Test>>(&dec, obj).err()
+    } else {
+        from_object::<(u8, i64, f32, bool)>(&dec, obj).err()
+    };
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &err.map(|e| e.to_string())).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_deNil(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap().nil_punning(true);
+    let out: Opts = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripNames(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Names = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripColls(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Colls = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripRaw(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Callback = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripMeta(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: WithMeta<Vec<i32>, Option<Source>> =
+        from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_serTypeMeta(env: JNIEnv, _: JClass) -> jobject {
+    let enc = Encoder::new(env).unwrap().type_meta(true);
+    let output =
+        to_object(&enc, &(Source { line: 1 }, Sink { line: 2 })).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripTagged(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone())
+        .unwrap()
+        .tag_newtype("UserId", "my/UserId");
+    let enc = Encoder::new(env)
+        .unwrap()
+        .tag_newtype("UserId", "my/UserId");
+    let output = match from_object::<Vec<UserId>>(&dec, obj) {
+        Ok(out) => to_object(&enc, &out),
+        Err(e) => to_object(&enc, &e.to_string()),
+    };
+    output.expect("serialisation failed!").into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripRecord(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Point = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap().record("Point", "test.Point");
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
 #[no_mangle]
 pub extern "system" fn Java_Test_roundtrip(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
     // making an encoder and a decoder uses a lot of local refs to
@@ -79,3 +385,304 @@ pub extern "system" fn Java_Test_roundtrip(env: JNIEnv, _: JClass, obj: JObject)
     let output = to_object(&enc, &out).expect("serialisation failed!");
     output.into_inner()
 }
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripBean(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap().bean("Person", "Person");
+    let out: Person = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap().bean("Person", "Person");
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripLine(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap().bean("Line", "Line");
+    let out: Line = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap().bean("Line", "Line");
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+/// `person` and `line` written back with canonical numbers, which
+/// must be converted for their `int` and `byte` setters and
+/// components, and the error for a `level` beyond a Java `byte`.
+#[no_mangle]
+pub extern "system" fn Java_Test_canonicalBeans(
+    env: JNIEnv,
+    _: JClass,
+    person: JObject,
+    line: JObject,
+) -> jobject {
+    let dec = Decoder::new(env.clone())
+        .unwrap()
+        .bean("Person", "Person")
+        .bean("Line", "Line");
+    let mut person: Person = from_object(&dec, person).expect("deserialisation failed");
+    let line: Line = from_object(&dec, line).expect("deserialisation failed");
+    let enc = Encoder::new(env.clone())
+        .unwrap()
+        .canonical_numbers(true)
+        .bean("Person", "Person")
+        .bean("Line", "Line");
+    let raw = |obj| Raw::new(env.new_global_ref(obj).unwrap());
+    let person_obj = raw(to_object(&enc, &person).expect("serialisation failed!"));
+    let line_obj = raw(to_object(&enc, &line).expect("serialisation failed!"));
+    person.level = 200;
+    let err = to_object(&enc, &person).unwrap_err().to_string();
+    let output = to_object(&enc, &(person_obj, line_obj, err)).expect("serialisation failed!");
+    output.into_inner()
+}
+
+/// The errors for a missing accessor when reading `obj` (a `Person` or
+/// a `Line`), and for a missing setter or record component when writing
+/// the same class.
+#[no_mangle]
+pub extern "system" fn Java_Test_beanErrors(
+    env: JNIEnv,
+    _: JClass,
+    obj: JObject,
+    class: JObject,
+) -> jobject {
+    let class: String = env.get_string(class.into()).unwrap().into();
+    let dec = Decoder::new(env.clone()).unwrap().bean("Badge", &class);
+    let read = from_object::<Badge>(&dec, obj).unwrap_err();
+    let enc = Encoder::new(env).unwrap().bean("Badge", &class);
+    let badge = Badge {
+        first_name: "Ann".into(),
+        nickname: "A".into(),
+    };
+    let write = to_object(&enc, &badge).unwrap_err();
+    let output =
+        to_object(&enc, &(read.to_string(), write.to_string())).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripEnum(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Option<TimeUnit> = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env)
+        .unwrap()
+        .java_enum("TimeUnit", "java.util.concurrent.TimeUnit");
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_enumError(env: JNIEnv, _: JClass) -> jobject {
+    let enc = Encoder::new(env)
+        .unwrap()
+        .java_enum("TimeUnit", "java.util.concurrent.TimeUnit");
+    let err = to_object(&enc, &TimeUnit::Fortnights).unwrap_err();
+    let output = to_object(&enc, &err.to_string()).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripTimes(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Times = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripUuids(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Owners = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripNums(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Amounts = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_toColumns(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let rows: Vec<Trade> = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_columns(&enc, &rows).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_fromColumns(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let rows: Vec<Trade> = from_columns(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &rows).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_callFn(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let enc = Encoder::new(env).unwrap();
+    let call: Call = from_object(&dec, obj).expect("deserialisation failed");
+    let ys = call
+        .xs
+        .iter()
+        .map(|x| call.f.invoke::<i64, _>(&enc, &dec, &(x,)))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("call failed");
+    let output = to_object(&enc, &ys).expect("serialisation failed!");
+    output.into_inner()
+}
+
+/// Calls a var, returning the result or the error message.
+#[no_mangle]
+pub extern "system" fn Java_Test_callVar(
+    env: JNIEnv,
+    _: JClass,
+    name: JObject,
+    args: JObject,
+) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let enc = Encoder::new(env).unwrap();
+    let name: String = from_object(&dec, name).expect("deserialisation failed");
+    let args: Vec<Raw> = from_object(&dec, args).expect("deserialisation failed");
+    let output = match Fn::var(&dec, &name).and_then(|f| f.invoke::<Raw, _>(&enc, &dec, &args)) {
+        Ok(out) => to_object(&enc, &out),
+        Err(e) => to_object(&enc, &e.to_string()),
+    };
+    output.expect("serialisation failed!").into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_rustFns(env: JNIEnv, _: JClass) -> jobject {
+    let fns = RustFns {
+        add: RustFn::new(|(a, b): (i64, i64)| Ok(a + b)),
+        // orders strings by length
+        compare: RustFn::new(|(a, b): (String, String)| Ok(a.len().cmp(&b.len()) as i32)),
+        sum: RustFn::new(|xs: Vec<i64>| Ok(xs.iter().sum::<i64>())),
+        half: RustFn::new(|(x,): (f32,)| Ok(x / 2.0)),
+    };
+    let enc = Encoder::new(env).unwrap().canonical_numbers(true);
+    let output = to_object(&enc, &fns).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_lazySquares(env: JNIEnv, _: JClass, chunk_size: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap().canonical_numbers(true);
+    let squares = (0..).map(|i: i32| i * i);
+    let output = serde_clj::lazy_seq_chunked(&enc, squares, chunk_size as usize)
+        .expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_sumLongs(env: JNIEnv, _: JClass, xs: JObject) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let enc = Encoder::new(env).unwrap();
+    let sum = serde_clj::iter_from_object::<i64>(&dec, xs).sum::<serde_clj::Result<i64>>();
+    let output = match sum {
+        Ok(sum) => to_object(&enc, &sum),
+        Err(e) => to_object(&enc, &e.to_string()),
+    };
+    output.expect("serialisation failed!").into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripMatrices(
+    env: JNIEnv,
+    _: JClass,
+    obj: JObject,
+    shaped: jboolean,
+) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Matrices = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::new(env).unwrap().shaped_arrays(shaped != 0);
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_matricesAfterJson(
+    env: JNIEnv,
+    _: JClass,
+    obj: JObject,
+) -> jobject {
+    let dec = Decoder::new(env.clone()).unwrap();
+    let out: Matrices = from_object(&dec, obj).expect("deserialisation failed");
+    // leaves what DirectBuffer stashes for our Serializer behind
+    let json = serde_json::to_string(&DirectBuffer::new(vec![1u8, 2, 3])).unwrap();
+    let enc = Encoder::new(env).unwrap().shaped_arrays(true);
+    let output = to_object(&enc, &(json, out)).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_serJava(env: JNIEnv, _: JClass, n: jint) -> jobject {
+    let enc = Encoder::java(env).unwrap();
+    let vec = make_tests(n);
+    let output = to_object(&enc, &vec).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_roundtripJava(env: JNIEnv, _: JClass, obj: JObject) -> jobject {
+    env.ensure_local_capacity(64)
+        .expect("failed increasing capacity");
+    let dec = Decoder::java(env.clone())
+        .unwrap()
+        .coercion(Coercion::Strict);
+    let out: Vec<Test> = from_object(&dec, obj).expect("deserialisation failed");
+    let enc = Encoder::java(env).unwrap();
+    let output = to_object(&enc, &out).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_buffer(env: JNIEnv, _: JClass, n: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap();
+    let buf = DirectBuffer::new((0..n).map(f64::from).collect::<Vec<_>>());
+    let output = to_object(&enc, &buf).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_sumBytes(env: JNIEnv, _: JClass, obj: JObject) -> jint {
+    let dec = Decoder::new(env).unwrap();
+    let bytes: &[u8] = from_object(&dec, obj).expect("deserialisation failed");
+    bytes.iter().map(|&b| jint::from(b)).sum()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_sumByteVec(env: JNIEnv, _: JClass, obj: JObject) -> jint {
+    let dec = Decoder::new(env).unwrap();
+    let bytes: Vec<u8> = from_object(&dec, obj).expect("deserialisation failed");
+    bytes.iter().map(|&b| jint::from(b)).sum()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_counter(env: JNIEnv, _: JClass, start: jint) -> jobject {
+    let enc = Encoder::new(env).unwrap();
+    let output = to_object(&enc, &Handle::new(Counter { start })).expect("serialisation failed!");
+    output.into_inner()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_counterStart(env: JNIEnv, _: JClass, obj: JObject) -> jint {
+    let dec = Decoder::new(env).unwrap();
+    let counter: Handle<Counter> = from_object(&dec, obj).expect("deserialisation failed");
+    counter.start
+}
+
+#[no_mangle]
+pub extern "system" fn Java_Test_isBuffer(env: JNIEnv, _: JClass, obj: JObject) -> jboolean {
+    let dec = Decoder::new(env).unwrap();
+    from_object::<Handle<DirectBuffer>>(&dec, obj)
+        .is_ok()
+        .into()
+}
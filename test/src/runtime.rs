@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use serde_clj::{Error, Keyword, Runtime};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+struct Person {
+    name: String,
+    age: u32,
+    tags: Vec<String>,
+}
+
+fn main() {
+    let classpath = std::env::args().nth(1).expect("usage: runtime <classpath>");
+    let runtime = Runtime::new(&std::env::split_paths(&classpath).collect::<Vec<_>>())
+        .expect("failed to start the JVM");
+
+    let sum: i64 = runtime.call("clojure.core/+", &(1, 2, 3)).unwrap();
+    assert_eq!(sum, 6);
+    let zero: i64 = runtime.call("clojure.core/+", &()).unwrap();
+    assert_eq!(zero, 0);
+
+    runtime.require("clojure.string").unwrap();
+    let s: String = runtime
+        .call("clojure.string/upper-case", &("hello",))
+        .unwrap();
+    assert_eq!(s, "HELLO");
+
+    let person = Person {
+        name: "Ann".into(),
+        age: 42,
+        tags: vec!["a".into()],
+    };
+    let older: Person = runtime
+        .call("clojure.core/assoc", &(&person, Keyword::new("age"), 43))
+        .unwrap();
+    assert_eq!(older.age, 43);
+
+    let keys: Vec<String> = runtime
+        .call("clojure.core/keys", &(BTreeMap::from([("x", 1)]),))
+        .unwrap();
+    assert_eq!(keys, vec!["x"]);
+
+    match runtime.call::<i64, _>("clojure.core//", &(1, 0)) {
+        Err(Error::Exception(desc)) => assert!(desc.contains("ArithmeticException"), "{}", desc),
+        other => panic!("expected an exception, got {:?}", other),
+    }
+
+    let back: Person = runtime.call("clojure.core/identity", &(&person,)).unwrap();
+    assert_eq!(back, person);
+    println!("runtime tests passed");
+}
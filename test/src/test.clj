@@ -1,5 +1,5 @@
 (ns test
-  (:import Test))
+  (:import Test Person Line))
 
 ;; Serialisation
 
@@ -10,10 +10,47 @@
       v (Test/ser n)]
   (assert (= (count v) n)))
 
+;; Only Longs and Doubles in canonical mode
+(assert (instance? Float (:a_float (first (Test/ser 1)))))
+
+(let [v (Test/serCanonical 1)]
+  (assert (every? #(or (instance? Long %) (instance? Double %))
+                  (filter number? (tree-seq coll? seq v))))
+  (assert (instance? Double (:a_float (first v))))
+  (Test/de v))
+
+;; No :another_field nil
+(let [v (Test/serOmitNil 1)]
+  (assert (not (contains? (first v) :another_field)))
+  (Test/de v))
+
 ;; Deserialisation
 
 (Test/de (Test/ser 1))
 
+;; Strict mode accepts exactly what we serialise
+(Test/deStrict (Test/ser 1))
+
+(let [t (first (Test/ser 1))]
+  (assert (nil? (Test/deError [t] true)))
+  (assert (= "ExpectedString" (Test/deError [(assoc t :a_string :kw)] true)))
+  ;; `tuple` is an (i32, String)
+  (assert (= "too many elements, expected a tuple of size 2"
+             (Test/deError [(assoc t :tuple [(int 4) "hey" "you"])] true)))
+  (assert (= "invalid length 1, expected a tuple of size 2"
+             (Test/deError [(assoc t :tuple [(int 4)])] true)))
+  (assert (= "ExpectedInteger" (Test/deError [(assoc t :tuple [4 "hey"])] true))))
+
+(assert (= [42 7 1.5 true] (Test/deLenient ["42" 7N 1.5M "true"])))
+(assert (= [42 7 0.25 true] (Test/deLenient [42.0 7.0M 1/4 "true"])))
+(assert (= "ExpectedInteger" (Test/deError ["forty-two" 7 1.5 true] false)))
+(assert (= "ExpectedInteger" (Test/deError [42 15/2 1.5 true] false)))
+(assert (= "ExpectedFloat" (Test/deError [42 7 "1.5x" true] false)))
+
+;; nil punning
+(assert (= {:names [] :counts {} :label ""} (Test/deNil nil)))
+(assert (= {:names [] :counts {} :label ""} (Test/deNil {:names nil :counts nil})))
+
 
 ;; roundtrip
 
@@ -24,3 +61,268 @@
 (let [v (Test/ser 1000)]
   (assert (= (fix-bytes v)
              (fix-bytes (Test/roundtrip v)))))
+
+;; empty collections stay empty (not [nil])
+(let [v (map #(assoc % :value [] :enumerate ()) (Test/ser 2))]
+  (assert (= (fix-bytes v)
+             (fix-bytes (Test/roundtrip v)))))
+
+;; keywords and symbols as values
+
+(let [v {:status :my.ns/ok
+         :f 'clojure.core/inc
+         :index {:a 1 :b/c 2}}]
+  (assert (= v (Test/roundtripNames v))))
+
+;; collection types
+
+(let [v {:list '(3 1 2)
+         :set #{"a" "b"}
+         :sorted_set (sorted-set 3 1 2)
+         :sorted_map (sorted-map "b" 2 "a" 1)}
+      out (Test/roundtripColls v)]
+  (assert (= v out))
+  (assert (list? (:list out)))
+  (assert (set? (:set out)))
+  (assert (sorted? (:sorted_set out)))
+  (assert (sorted? (:sorted_map out)))
+  (assert (= ["a" "b"] (keys (:sorted_map out)))))
+
+;; raw java objects pass through untouched
+
+(let [f (fn [x] (inc x))
+      out (Test/roundtripRaw {:name "inc" :f f})]
+  (assert (identical? f (:f out)))
+  (assert (= "inc" (:name out))))
+
+;; metadata
+
+(let [out (Test/roundtripMeta (with-meta [1 2] {:line 3}))]
+  (assert (= [1 2] out))
+  (assert (= {:line 3} (meta out))))
+
+(assert (nil? (meta (Test/roundtripMeta [1 2]))))
+
+(assert (= [{:rust/type "Source"} {:rust/type "testlib::Sink"}]
+           (map meta (Test/serTypeMeta))))
+
+;; tagged newtypes
+
+(let [out (Test/roundtripTagged [(tagged-literal 'my/UserId 42) 7])]
+  (assert (= [(tagged-literal 'my/UserId 42) (tagged-literal 'my/UserId 7)] out))
+  (assert (= "[#my/UserId 42 #my/UserId 7]" (pr-str out))))
+
+(assert (= "expected tag #my/UserId, got #other/Tag"
+           (Test/roundtripTagged [(tagged-literal 'other/Tag 42)])))
+
+;; records
+
+(defrecord Point [x y])
+
+(let [out (Test/roundtripRecord (->Point 1 2))]
+  (assert (instance? Point out))
+  (assert (= (->Point 1 2) out)))
+
+(assert (= (->Point 3 4) (Test/roundtripRecord {:x 3 :y 4})))
+
+;; java beans
+
+(let [out (Test/roundtripBean (doto (Person.)
+                                (.setFirstName "Ann")
+                                (.setAge 41)
+                                (.setActive true)))]
+  (assert (instance? Person out))
+  (assert (= ["Ann" 41 true] [(.getFirstName out) (.getAge out) (.isActive out)])))
+
+(let [out (Test/roundtripBean {:first_name "Bo" :age 7 :active false})]
+  (assert (= "Bo" (.getFirstName out))))
+
+(let [out (Test/roundtripLine (Line. 1 2 "a"))]
+  (assert (instance? Line out))
+  (assert (= (Line. 1 2 "a") out)))
+
+(assert (= (Line. 3 4 "b") (Test/roundtripLine {:from_x 3 :to_x 4 :label "b"})))
+
+;; canonical Longs reach int and byte setters and components, and the
+;; setAge(int) overload is picked over setAge(String)
+
+(let [[person line err] (Test/canonicalBeans (doto (Person.)
+                                               (.setFirstName "Cy")
+                                               (.setAge 30)
+                                               (.setLevel (byte 5)))
+                                             (Line. 5 6 "c"))]
+  (assert (= ["Cy" 30 5] [(.getFirstName person) (.getAge person) (.getLevel person)]))
+  (assert (= (Line. 5 6 "c") line))
+  (assert (= "field `level` doesn't fit in the byte parameter of Person" err)))
+
+;; missing accessors are named
+(assert (= ["Person has no accessor getNickname() or isNickname() for field `nickname`"
+            "Person has no setter setNickname() for field `nickname`"]
+           (Test/beanErrors (Person.) "Person")))
+(assert (= ["Line has no accessor firstName() for field `first_name`"
+            "record Line has no component `firstName` for field `first_name`"]
+           (Test/beanErrors (Line. 1 2 "a") "Line")))
+
+;; Optional and java enums
+
+(let [unit java.util.concurrent.TimeUnit/MINUTES]
+  (assert (identical? unit (Test/roundtripEnum (java.util.Optional/of unit))))
+  (assert (identical? unit (Test/roundtripEnum unit))))
+
+(assert (nil? (Test/roundtripEnum (java.util.Optional/empty))))
+(assert (= "java.util.concurrent.TimeUnit has no constant FORTNIGHTS" (Test/enumError)))
+
+;; timestamps and durations
+
+(let [out (Test/roundtripTimes {:created #inst "2020-01-02T03:04:05.678Z"
+                                :updated #inst "2020-01-02T03:04:05.678Z"
+                                :due (java.time.LocalDate/of 2020 2 29)
+                                :timeout (java.time.Duration/ofMillis 1500)})]
+  (assert (= #inst "2020-01-02T03:04:05.678Z" (:created out)))
+  (assert (= (java.time.Instant/parse "2020-01-02T03:04:05.678Z") (:updated out)))
+  (assert (= (java.time.LocalDate/of 2020 2 29) (:due out)))
+  (assert (= (java.time.Duration/ofMillis 1500) (:timeout out))))
+
+;; uuids
+
+(let [id #uuid "6f1c2d3e-4f50-4617-8899-aabbccddeeff"
+      out (Test/roundtripUuids {:id (str id) :owners {id "ann"}})]
+  (assert (= {:id id :owners {id "ann"}} out))
+  (assert (uuid? (:id out))))
+
+;; big numbers
+
+(let [out (Test/roundtripNums {:total 12345678901234567890123N
+                               :share -2/6
+                               :price 12.3400M})]
+  (assert (= {:total 12345678901234567890123N :share -1/3 :price 12.3400M} out))
+  (assert (= 4 (.scale ^BigDecimal (:price out)))))
+
+(let [out (Test/roundtripNums {:total 1 :share 2 :price 3})]
+  (assert (= {:total 1N :share 2N :price 3M} out)))
+
+;; matrices
+
+(let [in {:weights [[1.0 2.0 3.0] [4.0 5.0 6.0]] :counts [[[1 2]] [[3 4]]]}
+      out (Test/roundtripMatrices in false)
+      shaped (Test/roundtripMatrices in true)]
+  (assert (= in out))
+  (assert (= [2 3] (get-in shaped [:weights :shape])))
+  (assert (= [1.0 2.0 3.0 4.0 5.0 6.0] (vec (get-in shaped [:weights :data]))))
+  (assert (= Long/TYPE (.getComponentType (class (get-in shaped [:counts :data])))))
+  (assert (= in (Test/roundtripMatrices shaped false))))
+
+;; a value stashed for another serializer isn't picked up by ours
+
+(let [[json shaped] (Test/matricesAfterJson {:weights [[1.0 2.0 3.0] [4.0 5.0 6.0]]
+                                             :counts [[[1 2]] [[3 4]]]})]
+  (assert (= "[1,2,3]" json))
+  (assert (= [2 3] (get-in shaped [:weights :shape])))
+  (assert (= [2 1 2] (vec (get-in shaped [:counts :shape])))))
+
+(assert (= {:weights [[]] :counts []}
+           (Test/roundtripMatrices {:weights [[]] :counts []} false)))
+
+;; columns
+
+(let [rows [{:id 1 :symbol "AB" :price 1.5 :qty 10 :venues ["x"]}
+            {:id 2 :symbol "CD" :price 2.5 :qty nil :venues []}]
+      cols (Test/toColumns rows)]
+  (assert (= [1 2] (vec (:id cols))))
+  (assert (= Long/TYPE (.getComponentType (class (:id cols)))))
+  (assert (= ["AB" "CD"] (vec (:symbol cols))))
+  (assert (= [1.5 2.5] (vec (:price cols))))
+  (assert (= Integer/TYPE (.getComponentType (class (:qty cols)))))
+  (assert (= [false true] (vec (get-in (meta cols) [:missing :qty]))))
+  (assert (nil? (get-in (meta cols) [:missing :price])))
+  (assert (= [["x"] []] (vec (:venues cols))))
+  (assert (= rows (Test/fromColumns cols)))
+  (assert (= [{:id 3 :symbol "EF" :price 4.0 :qty nil :venues []}]
+             (Test/fromColumns {:id [3] :symbol ["EF"] :price [4.0] :qty [nil] :venues [[]]}))))
+
+(assert (= [] (Test/fromColumns (Test/toColumns []))))
+
+;; calling fns
+
+(assert (= [2 3 4] (Test/callFn {:f inc :xs [1 2 3]})))
+(assert (= [1 4 9] (Test/callFn {:f #(* % %) :xs [1 2 3]})))
+(assert (= "a1" (Test/callVar "clojure.core/str" ["a" 1])))
+(assert (= 4 (Test/callVar "clojure.core/count" [[1 2 3 4]])))
+(assert (re-find #"ArithmeticException" (Test/callVar "clojure.core//" [1 0])))
+
+(let [{:keys [add compare sum half]} (Test/rustFns)]
+  (assert (fn? add))
+  (assert (= 3 (add 1 2)))
+  (assert (= 10 (reduce add [1 2 3 4])))
+  (assert (= 6 (apply add [2 4])))
+  (assert (= ["a" "bb" "ccc"] (sort compare ["ccc" "a" "bb"])))
+  (assert (= :thrown (try (add "x" 1) (catch RuntimeException _ :thrown))))
+  (assert (= [5 7] (Test/callFn {:f (partial add 4) :xs [1 3]})))
+  (assert (= 0 (sum)))
+  (assert (= 0 (apply sum [])))
+  (assert (= 6 (sum 1 2 3)))
+  ;; results use the options of the Encoder which made the fn
+  (assert (instance? Double (half 3.0))))
+
+;; lazy seqs
+
+(let [squares (Test/lazySquares 4)]
+  (assert (seq? squares))
+  (assert (not (realized? squares)))
+  (assert (= [0 1 4 9 16] (take 5 squares)))
+  (assert (= 285 (reduce + (take 10 squares))))
+  (assert (= 1 (count (filter #(= 10000 %) (take 101 squares)))))
+  (assert (chunked-seq? (seq squares))))
+
+(assert (= 6 (Test/sumLongs [1 2 3])))
+(assert (= 499999500000 (Test/sumLongs (range 1000000))))
+(assert (= 285 (Test/sumLongs (take 10 (Test/lazySquares 8)))))
+(assert (= 0 (Test/sumLongs ())))
+(assert (= "ExpectedArray" (Test/sumLongs nil)))
+(assert (= "ExpectedArray" (Test/sumLongs 5)))
+(assert (= "ExpectedInteger" (Test/sumLongs [1 "two" 3])))
+
+;; plain java collections
+
+(let [v (Test/serJava 2)
+      m (first v)]
+  (assert (instance? java.util.ArrayList v))
+  (assert (instance? java.util.LinkedHashMap m))
+  (assert (= "test" (get m "a_string")))
+  (assert (= "Zero" (first (get m "enumerate"))))
+  (assert (= 2 (count (Test/roundtripJava v)))))
+
+;; direct buffers
+
+(let [buf (Test/buffer 100)]
+  (assert (instance? java.nio.DoubleBuffer buf))
+  (assert (.isReadOnly buf))
+  (assert (= (range 100) (map long (repeatedly 100 #(.get buf))))))
+
+(let [buf (doto (java.nio.ByteBuffer/allocateDirect 3)
+            (.put (byte-array [1 2 3]))
+            (.flip))]
+  (assert (= 6 (Test/sumBytes buf)))
+  (assert (= 6 (Test/sumByteVec buf))))
+
+;; only position..limit is read, and heap buffers are copied
+(let [buf (doto (java.nio.ByteBuffer/allocateDirect 5)
+            (.put (byte-array [1 2 3 4 5]))
+            (.position 1)
+            (.limit 4))]
+  (assert (= 9 (Test/sumBytes buf)))
+  (assert (= 9 (Test/sumBytes (.slice buf))))
+  (assert (= 9 (Test/sumByteVec buf))))
+
+(let [buf (doto (java.nio.ByteBuffer/wrap (byte-array [1 2 3 4 5]))
+            (.position 2))]
+  (assert (= 12 (Test/sumBytes buf)))
+  (assert (= 12 (Test/sumByteVec buf)))
+  (assert (= 2 (.position buf))))
+
+;; opaque handles
+
+(let [h (Test/counter 42)]
+  (assert (instance? serde_clj.Handle h))
+  (assert (= 42 (Test/counterStart h)))
+  (assert (not (Test/isBuffer h))))
@@ -1,5 +1,8 @@
 #!/bin/bash
 set -ex
-javac src/Test.java
+javac -cp "$(clojure -Spath)" -d src ../java/serde_clj/*.java
+javac src/Test.java src/Person.java src/Line.java
 cargo build
 clojure -J-Djava.library.path="target/debug" -J-Xcheck:jni src/test.clj
+# the embedded runtime, which needs to find libjvm
+LD_LIBRARY_PATH="$JAVA_HOME/lib/server" cargo run --bin runtime -- "$(clojure -Spath)"